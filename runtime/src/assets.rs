@@ -0,0 +1,402 @@
+use support::{
+    decl_module,
+    decl_storage,
+    decl_event,
+    StorageMap,
+    dispatch::Result,
+    ensure,
+    Parameter,
+    traits::Currency,
+    traits::ReservableCurrency,
+};
+use system::{ ensure_signed, ensure_root };
+use rstd::prelude::*;
+use runtime_primitives::traits::{
+    Member,
+    SimpleArithmetic,
+    MaybeSerializeDebug,
+    StaticLookup,
+    Zero,
+    CheckedAdd,
+    CheckedSub,
+};
+
+// asset id zero is the native currency held in the balances module,
+// every other asset id is held in this module's ledger
+pub trait Trait: system::Trait + balances::Trait {
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	// identifier of an asset, shared with the lending markets
+	type AssetId: Parameter + Member + SimpleArithmetic + Default + Copy + MaybeSerializeDebug;
+
+	// told of every transfer between accounts made through `transfer`
+	type OnTransfer: OnTransfer<Self::AccountId, Self::AssetId>;
+}
+
+// lets modules tracking the holders of an asset follow transfers they do not make
+pub trait OnTransfer<AccountId, AssetId> {
+    fn on_transfer(asset: AssetId, from: &AccountId, to: &AccountId);
+}
+
+impl<AccountId, AssetId> OnTransfer<AccountId, AssetId> for () {
+    fn on_transfer(_asset: AssetId, _from: &AccountId, _to: &AccountId) {}
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Assets {
+                // free balance of non-native assets, endowed in genesis config
+                Balances get(balance) config(balances): map (T::AssetId, T::AccountId) => T::Balance;
+
+                // balance of non-native assets set aside by other modules
+                Reserved get(reserved): map (T::AssetId, T::AccountId) => T::Balance;
+
+                // total amount of each non-native asset in existence
+                TotalIssuance get(total_issuance) build(|config: &GenesisConfig<T>| {
+                    let mut issuance: Vec<(T::AssetId, T::Balance)> = Vec::new();
+                    for &((asset, _), balance) in config.balances.iter() {
+                        match issuance.iter_mut().find(|(issued, _)| *issued == asset) {
+                            Some((_, total)) => *total = *total + balance,
+                            None => issuance.push((asset, balance)),
+                        }
+                    }
+                    issuance
+                }): map T::AssetId => T::Balance;
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		// Initializing events
+		fn deposit_event<T>() = default;
+
+                fn transfer(_origin, asset: T::AssetId, dest: <T::Lookup as StaticLookup>::Source, value: T::Balance) -> Result {
+                    let sender = ensure_signed(_origin)?;
+                    let dest = T::Lookup::lookup(dest)?;
+
+                    Self::make_transfer(asset, &sender, &dest, value)?;
+                    T::OnTransfer::on_transfer(asset, &sender, &dest);
+
+                    Self::deposit_event(RawEvent::Transferred(asset, sender, dest, value));
+
+                    Ok(())
+                }
+
+                fn mint(origin, asset: T::AssetId, dest: <T::Lookup as StaticLookup>::Source, value: T::Balance) -> Result {
+                    ensure_root(origin)?;
+                    let dest = T::Lookup::lookup(dest)?;
+
+                    // native currency is issued by the balances module
+                    ensure!(!asset.is_zero(), "Cannot mint the native asset.");
+
+                    Self::issue(asset, &dest, value)?;
+
+                    Self::deposit_event(RawEvent::Minted(asset, dest, value));
+
+                    Ok(())
+                }
+	}
+}
+
+impl<T: Trait> Module<T> {
+    pub fn free_balance(asset: T::AssetId, who: &T::AccountId) -> T::Balance {
+        if asset.is_zero() {
+            <balances::Module<T>>::free_balance(who)
+        } else {
+            Self::balance((asset, who.clone()))
+        }
+    }
+
+    pub fn reserved_balance(asset: T::AssetId, who: &T::AccountId) -> T::Balance {
+        if asset.is_zero() {
+            <balances::Module<T>>::reserved_balance(who)
+        } else {
+            Self::reserved((asset, who.clone()))
+        }
+    }
+
+    pub fn make_transfer(
+        asset: T::AssetId,
+        outgoing: &T::AccountId,
+        incoming: &T::AccountId,
+        value: T::Balance
+    ) -> Result {
+        if asset.is_zero() {
+            return <balances::Module<T> as Currency<_>>::transfer(outgoing, incoming, value);
+        }
+
+        if outgoing == incoming {
+            return Ok(());
+        }
+
+        let decr_outgoing = Self::balance((asset, outgoing.clone()))
+            .checked_sub(&value)
+            .ok_or("Insufficient asset balance for transfer")?;
+        let incr_incoming = Self::balance((asset, incoming.clone()))
+            .checked_add(&value)
+            .ok_or("Overflow encountered crediting asset transfer")?;
+
+        <Balances<T>>::insert((asset, outgoing.clone()), decr_outgoing);
+        <Balances<T>>::insert((asset, incoming.clone()), incr_incoming);
+
+        Ok(())
+    }
+
+    pub fn reserve(asset: T::AssetId, who: &T::AccountId, value: T::Balance) -> Result {
+        if asset.is_zero() {
+            return <balances::Module<T>>::reserve(who, value);
+        }
+
+        let decr_free = Self::balance((asset, who.clone()))
+            .checked_sub(&value)
+            .ok_or("Insufficient asset balance to reserve")?;
+        let incr_reserved = Self::reserved((asset, who.clone()))
+            .checked_add(&value)
+            .ok_or("Overflow encountered reserving asset")?;
+
+        <Balances<T>>::insert((asset, who.clone()), decr_free);
+        <Reserved<T>>::insert((asset, who.clone()), incr_reserved);
+
+        Ok(())
+    }
+
+    // moves up to `value` back to free balance, as `ReservableCurrency` does
+    pub fn unreserve(asset: T::AssetId, who: &T::AccountId, value: T::Balance) {
+        if asset.is_zero() {
+            <balances::Module<T>>::unreserve(who, value);
+            return;
+        }
+
+        let reserved = Self::reserved((asset, who.clone()));
+        let actual = if value < reserved { value } else { reserved };
+
+        <Reserved<T>>::insert((asset, who.clone()), reserved - actual);
+        <Balances<T>>::mutate((asset, who.clone()), |free| *free = free.clone() + actual);
+    }
+
+    // moves reserved balance of `slashed` into the free balance of `beneficiary`
+    pub fn repatriate_reserved(
+        asset: T::AssetId,
+        slashed: &T::AccountId,
+        beneficiary: &T::AccountId,
+        value: T::Balance
+    ) -> Result {
+        if asset.is_zero() {
+            // balances moves what it can and reports the rest, all or nothing here
+            ensure!(<balances::Module<T>>::reserved_balance(slashed) >= value,
+                    "Insufficient reserved asset balance to repatriate");
+            <balances::Module<T>>::repatriate_reserved(slashed, beneficiary, value)?;
+            return Ok(());
+        }
+
+        let decr_reserved = Self::reserved((asset, slashed.clone()))
+            .checked_sub(&value)
+            .ok_or("Insufficient reserved asset balance to repatriate")?;
+        let incr_free = Self::balance((asset, beneficiary.clone()))
+            .checked_add(&value)
+            .ok_or("Overflow encountered repatriating asset")?;
+
+        <Reserved<T>>::insert((asset, slashed.clone()), decr_reserved);
+        <Balances<T>>::insert((asset, beneficiary.clone()), incr_free);
+
+        Ok(())
+    }
+
+    // creates `value` of a non-native asset in the account of `who`
+    pub fn issue(asset: T::AssetId, who: &T::AccountId, value: T::Balance) -> Result {
+        ensure!(!asset.is_zero(), "Cannot issue the native asset.");
+
+        let incr_issuance = Self::total_issuance(asset)
+            .checked_add(&value)
+            .ok_or("Overflow encountered issuing asset")?;
+        let incr_free = Self::balance((asset, who.clone()))
+            .checked_add(&value)
+            .ok_or("Overflow encountered issuing asset")?;
+
+        <TotalIssuance<T>>::insert(asset, incr_issuance);
+        <Balances<T>>::insert((asset, who.clone()), incr_free);
+
+        Ok(())
+    }
+
+    // destroys `value` of a non-native asset from the free balance of `who`
+    pub fn burn(asset: T::AssetId, who: &T::AccountId, value: T::Balance) -> Result {
+        ensure!(!asset.is_zero(), "Cannot burn the native asset.");
+
+        let decr_free = Self::balance((asset, who.clone()))
+            .checked_sub(&value)
+            .ok_or("Insufficient asset balance to burn")?;
+
+        <TotalIssuance<T>>::mutate(asset, |issuance| *issuance = issuance.clone() - value);
+        <Balances<T>>::insert((asset, who.clone()), decr_free);
+
+        Ok(())
+    }
+}
+
+decl_event!(
+	pub enum Event<T>
+        where
+            <T as system::Trait>::AccountId,
+            <T as balances::Trait>::Balance,
+            <T as Trait>::AssetId,
+        {
+                Transferred(AssetId, AccountId, AccountId, Balance),
+                Minted(AssetId, AccountId, Balance),
+	}
+);
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use runtime_io::with_externalities;
+	use primitives::{H256, Blake2Hasher};
+	use support::{
+            impl_outer_origin,
+            assert_ok,
+            assert_noop
+        };
+	use runtime_primitives::{
+		BuildStorage,
+		traits::{BlakeTwo256, IdentityLookup},
+		testing::{Digest, DigestItem, Header}
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for Test {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+
+	impl system::Trait for Test {
+		type Origin = Origin;
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type Digest = Digest;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type Log = DigestItem;
+	}
+
+        impl balances::Trait for Test {
+                type Balance = u128;
+                type OnFreeBalanceZero = ();
+                type OnNewAccount = ();
+                type Event = ();
+
+                type TransactionPayment = ();
+                type DustRemoval = ();
+                type TransferPayment = ();
+	}
+
+	impl super::Trait for Test {
+		type Event = ();
+		type AssetId = u32;
+		type OnTransfer = ();
+	}
+
+	type Assets = Module<Test>;
+	type Balances = balances::Module<Test>;
+
+	fn build() -> runtime_io::TestExternalities<Blake2Hasher> {
+		let mut t = system::GenesisConfig::<Test>::default()
+                    .build_storage()
+                    .unwrap()
+                    .0;
+                t.extend(balances::GenesisConfig::<Test> {
+                    transaction_base_fee: 0,
+                    transaction_byte_fee: 0,
+                    existential_deposit: 0,
+                    transfer_fee: 0,
+                    creation_fee: 0,
+                    balances: vec![(1, 1_000), (2, 1_000)],
+                    vesting: vec![],
+                    }
+                    .build_storage()
+                    .unwrap()
+                    .0,
+                    );
+
+                t.extend(
+                    GenesisConfig::<Test> {
+                        balances: vec![((1, 1), 500)],
+                    }
+                    .build_storage()
+                    .unwrap()
+                    .0,
+                );
+                t.into()
+	}
+
+        #[test]
+        fn user_can_transfer_asset() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Assets::transfer(Origin::signed(1), 1, 2, 200));
+                assert_eq!(Assets::free_balance(1, &1), 300);
+                assert_eq!(Assets::free_balance(1, &2), 200);
+
+                assert_noop!(Assets::transfer(Origin::signed(2), 1, 1, 201),
+                             "Insufficient asset balance for transfer");
+            });
+        }
+
+        #[test]
+        fn native_asset_is_held_in_balances() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Assets::transfer(Origin::signed(1), 0, 2, 200));
+                assert_eq!(Balances::free_balance(&2), 1_200);
+                assert_eq!(Assets::free_balance(0, &2), 1_200);
+
+                assert_ok!(Assets::reserve(0, &2, 100));
+                assert_eq!(Balances::reserved_balance(&2), 100);
+            });
+        }
+
+        #[test]
+        fn reserved_asset_can_be_repatriated() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Assets::reserve(1, &1, 300));
+                assert_eq!(Assets::free_balance(1, &1), 200);
+                assert_eq!(Assets::reserved_balance(1, &1), 300);
+
+                assert_ok!(Assets::repatriate_reserved(1, &1, &2, 100));
+                assert_eq!(Assets::reserved_balance(1, &1), 200);
+                assert_eq!(Assets::free_balance(1, &2), 100);
+
+                // unreserving more than is reserved releases what there is
+                Assets::unreserve(1, &1, 1_000);
+                assert_eq!(Assets::free_balance(1, &1), 400);
+                assert_eq!(Assets::reserved_balance(1, &1), 0);
+            });
+        }
+
+        #[test]
+        fn only_root_can_mint() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Assets::mint(Origin::signed(1), 1, 2, 100),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Assets::mint(Origin::ROOT, 0, 2, 100),
+                             "Cannot mint the native asset.");
+                assert_ok!(Assets::mint(Origin::ROOT, 1, 2, 100));
+                assert_eq!(Assets::free_balance(1, &2), 100);
+                assert_eq!(Assets::total_issuance(1), 600);
+            });
+        }
+
+        #[test]
+        fn burning_reduces_issuance() {
+            with_externalities(&mut build(), || {
+                assert_eq!(Assets::total_issuance(1), 500);
+                assert_noop!(Assets::burn(1, &1, 501), "Insufficient asset balance to burn");
+                assert_ok!(Assets::burn(1, &1, 200));
+                assert_eq!(Assets::total_issuance(1), 300);
+                assert_eq!(Assets::free_balance(1, &1), 300);
+            });
+        }
+}
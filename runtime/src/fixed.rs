@@ -0,0 +1,413 @@
+use parity_codec::{ Encode, Decode };
+use runtime_primitives::Perbill;
+#[cfg(feature = "std")]
+use serde::{ Serialize, Deserialize };
+
+// number of decimals carried by a Fixed
+pub const DECIMALS: u32 = 18;
+
+// the inner value of one
+const ONE: u128 = 1_000_000_000_000_000_000;
+
+// Perbill's precision, nine decimals short of a Fixed
+const BILLION: u128 = 1_000_000_000;
+
+// direction in which a result that falls between
+// two representable values is rounded
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Rounding {
+    // towards zero
+    Down,
+    // away from zero
+    Up,
+    // to the closer value, halves rounding up
+    Nearest,
+}
+
+// unsigned fixed-point number with 18 decimals, used for rates,
+// interest indices, exchange rates and utilization.
+// arithmetic is checked, returning `None` on overflow or division
+// by zero, and every lossy operation names how it rounds
+#[derive(Encode, Decode, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct Fixed(u128);
+
+impl Fixed {
+    // a Fixed from its inner value, in units of 10^-18
+    pub const fn from_parts(parts: u128) -> Self {
+        Fixed(parts)
+    }
+
+    pub fn into_parts(self) -> u128 {
+        self.0
+    }
+
+    pub fn zero() -> Self {
+        Fixed(0)
+    }
+
+    pub fn one() -> Self {
+        Fixed(ONE)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn from_integer(n: u128) -> Option<Self> {
+        n.checked_mul(ONE).map(Fixed)
+    }
+
+    // n / d, None when d is zero or the quotient does not fit
+    pub fn from_rational(n: u128, d: u128, rounding: Rounding) -> Option<Self> {
+        mul_div(n, ONE, d, rounding).map(Fixed)
+    }
+
+    // exact, as a Perbill has fewer decimals
+    pub fn from_perbill(p: Perbill) -> Self {
+        Fixed((p * BILLION as u64) as u128 * BILLION)
+    }
+
+    // saturating at 100%
+    pub fn to_perbill(self, rounding: Rounding) -> Perbill {
+        let billionths = div_rounded(self.0, BILLION, rounding).min(BILLION);
+        Perbill::from_billionths(billionths as u32)
+    }
+
+    // integer part, rounded
+    pub fn to_integer(self, rounding: Rounding) -> u128 {
+        div_rounded(self.0, ONE, rounding)
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Fixed)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Fixed)
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        Fixed(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Fixed(self.0.saturating_sub(other.0))
+    }
+
+    pub fn checked_mul(self, other: Self, rounding: Rounding) -> Option<Self> {
+        mul_div(self.0, other.0, ONE, rounding).map(Fixed)
+    }
+
+    pub fn checked_div(self, other: Self, rounding: Rounding) -> Option<Self> {
+        mul_div(self.0, ONE, other.0, rounding).map(Fixed)
+    }
+
+    // n * self, as an integer
+    pub fn checked_mul_int(self, n: u128, rounding: Rounding) -> Option<u128> {
+        mul_div(n, self.0, ONE, rounding)
+    }
+
+    // n / self, as an integer
+    pub fn checked_div_int(self, n: u128, rounding: Rounding) -> Option<u128> {
+        mul_div(n, ONE, self.0, rounding)
+    }
+}
+
+// a * b / c, computed over 256 bits so that only a quotient
+// too large for u128 overflows; None when c is zero
+pub fn mul_div(a: u128, b: u128, c: u128, rounding: Rounding) -> Option<u128> {
+    if c == 0 {
+        return None;
+    }
+
+    let (high, low) = widening_mul(a, b);
+    if high == 0 {
+        return Some(div_rounded(low, c, rounding));
+    }
+
+    // the quotient fits in 128 bits only while the high half is below c
+    if high >= c {
+        return None;
+    }
+
+    // long division, bringing down one bit of the low half at a time;
+    // the remainder stays below c, and `carry` holds the bit shifted
+    // out of it whenever it grows past 128 bits in between
+    let mut quotient: u128 = 0;
+    let mut remainder = high;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+
+    round(quotient, remainder, c, rounding)
+}
+
+// full 256 bit product as (high, low) halves
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::max_value() as u128;
+
+    let (a_high, a_low) = (a >> 64, a & MASK);
+    let (b_high, b_low) = (b >> 64, b & MASK);
+
+    let low_low = a_low * b_low;
+    let low_high = a_low * b_high;
+    let high_low = a_high * b_low;
+    let high_high = a_high * b_high;
+
+    // at most three 64 bit values, which cannot overflow
+    let middle = (low_low >> 64) + (low_high & MASK) + (high_low & MASK);
+
+    let low = (low_low & MASK) | (middle << 64);
+    let high = high_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+
+    (high, low)
+}
+
+fn div_rounded(n: u128, d: u128, rounding: Rounding) -> u128 {
+    // only rounding up a quotient of u128::max_value() can overflow,
+    // which takes a divisor of one and so leaves no remainder
+    round(n / d, n % d, d, rounding).unwrap_or(u128::max_value())
+}
+
+fn round(quotient: u128, remainder: u128, divisor: u128, rounding: Rounding) -> Option<u128> {
+    let round_up = match rounding {
+        Rounding::Down => false,
+        Rounding::Up => remainder > 0,
+        // remainder >= divisor / 2, without losing the odd half
+        Rounding::Nearest => remainder >= divisor - remainder,
+    };
+
+    if round_up {
+        quotient.checked_add(1)
+    } else {
+        Some(quotient)
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: u128 = u128::max_value();
+
+    fn fixed(n: u128) -> Fixed {
+        Fixed::from_integer(n).unwrap()
+    }
+
+    #[test]
+    fn constants_are_consistent() {
+        assert_eq!(ONE, 10u128.pow(DECIMALS));
+        assert_eq!(Fixed::one().into_parts(), ONE);
+        assert!(Fixed::zero().is_zero());
+        assert!(!Fixed::one().is_zero());
+        assert_eq!(Fixed::default(), Fixed::zero());
+        assert_eq!(Fixed::from_parts(ONE), Fixed::one());
+    }
+
+    #[test]
+    fn integers_convert_within_range() {
+        assert_eq!(Fixed::from_integer(0), Some(Fixed::zero()));
+        assert_eq!(Fixed::from_integer(1), Some(Fixed::one()));
+        assert_eq!(fixed(42).to_integer(Rounding::Down), 42);
+
+        let largest = MAX / ONE;
+        assert_eq!(Fixed::from_integer(largest).map(Fixed::into_parts), Some(largest * ONE));
+        assert_eq!(Fixed::from_integer(largest + 1), None);
+    }
+
+    #[test]
+    fn integer_part_rounds_by_mode() {
+        let two_and_a_half = Fixed::from_parts(5 * ONE / 2);
+        assert_eq!(two_and_a_half.to_integer(Rounding::Down), 2);
+        assert_eq!(two_and_a_half.to_integer(Rounding::Up), 3);
+        assert_eq!(two_and_a_half.to_integer(Rounding::Nearest), 3);
+
+        let just_below_half = Fixed::from_parts(ONE / 2 - 1);
+        assert_eq!(just_below_half.to_integer(Rounding::Down), 0);
+        assert_eq!(just_below_half.to_integer(Rounding::Up), 1);
+        assert_eq!(just_below_half.to_integer(Rounding::Nearest), 0);
+
+        // exact values are unaffected by the mode
+        for rounding in &[Rounding::Down, Rounding::Up, Rounding::Nearest] {
+            assert_eq!(fixed(7).to_integer(*rounding), 7);
+            assert_eq!(Fixed::zero().to_integer(*rounding), 0);
+        }
+
+        assert_eq!(Fixed::from_parts(MAX).to_integer(Rounding::Up), MAX / ONE + 1);
+    }
+
+    #[test]
+    fn rationals_round_by_mode() {
+        assert_eq!(Fixed::from_rational(1, 2, Rounding::Down), Some(Fixed::from_parts(ONE / 2)));
+        assert_eq!(Fixed::from_rational(3, 1, Rounding::Down), Some(fixed(3)));
+
+        // a third falls between two values of the last decimal
+        let third = ONE / 3;
+        assert_eq!(Fixed::from_rational(1, 3, Rounding::Down), Some(Fixed::from_parts(third)));
+        assert_eq!(Fixed::from_rational(1, 3, Rounding::Up), Some(Fixed::from_parts(third + 1)));
+        assert_eq!(Fixed::from_rational(1, 3, Rounding::Nearest), Some(Fixed::from_parts(third)));
+        assert_eq!(Fixed::from_rational(2, 3, Rounding::Nearest), Some(Fixed::from_parts(2 * third + 1)));
+
+        assert_eq!(Fixed::from_rational(1, 0, Rounding::Down), None);
+        assert_eq!(Fixed::from_rational(0, 0, Rounding::Down), None);
+        assert_eq!(Fixed::from_rational(0, 5, Rounding::Up), Some(Fixed::zero()));
+    }
+
+    #[test]
+    fn rationals_of_large_numbers_do_not_overflow_early() {
+        // n * ONE exceeds u128 while n / d does not
+        let n = MAX / 2;
+        assert_eq!(Fixed::from_rational(n, n, Rounding::Down), Some(Fixed::one()));
+        assert_eq!(Fixed::from_rational(n, n / 4, Rounding::Down).map(|r| r.to_integer(Rounding::Down)), Some(4));
+
+        // but a quotient beyond the range does
+        assert_eq!(Fixed::from_rational(MAX, 1, Rounding::Down), None);
+        assert_eq!(Fixed::from_rational(MAX / ONE + 1, 1, Rounding::Down), None);
+    }
+
+    #[test]
+    fn perbill_converts_exactly() {
+        assert_eq!(Fixed::from_perbill(Perbill::from_percent(100)), Fixed::one());
+        assert_eq!(Fixed::from_perbill(Perbill::from_percent(0)), Fixed::zero());
+        assert_eq!(Fixed::from_perbill(Perbill::from_percent(6)), Fixed::from_rational(6, 100, Rounding::Down).unwrap());
+        assert_eq!(Fixed::from_perbill(Perbill::from_billionths(1)), Fixed::from_parts(BILLION));
+
+        for billionths in &[0, 1, 999, 123_456_789, 999_999_999, 1_000_000_000] {
+            let rate = Perbill::from_billionths(*billionths);
+            assert_eq!(Fixed::from_perbill(rate).to_perbill(Rounding::Down), rate);
+        }
+    }
+
+    #[test]
+    fn perbill_rounds_and_saturates() {
+        let fraction = Fixed::from_parts(BILLION + BILLION / 2);
+        assert_eq!(fraction.to_perbill(Rounding::Down), Perbill::from_billionths(1));
+        assert_eq!(fraction.to_perbill(Rounding::Up), Perbill::from_billionths(2));
+        assert_eq!(fraction.to_perbill(Rounding::Nearest), Perbill::from_billionths(2));
+
+        assert_eq!(fixed(2).to_perbill(Rounding::Down), Perbill::from_percent(100));
+        assert_eq!(Fixed::from_parts(MAX).to_perbill(Rounding::Up), Perbill::from_percent(100));
+    }
+
+    #[test]
+    fn addition_and_subtraction_are_checked() {
+        assert_eq!(fixed(1).checked_add(fixed(2)), Some(fixed(3)));
+        assert_eq!(Fixed::from_parts(MAX).checked_add(Fixed::from_parts(1)), None);
+        assert_eq!(fixed(3).checked_sub(fixed(2)), Some(fixed(1)));
+        assert_eq!(fixed(2).checked_sub(fixed(3)), None);
+
+        assert_eq!(Fixed::from_parts(MAX).saturating_add(fixed(1)), Fixed::from_parts(MAX));
+        assert_eq!(fixed(2).saturating_sub(fixed(3)), Fixed::zero());
+    }
+
+    #[test]
+    fn multiplication_rounds_by_mode() {
+        assert_eq!(fixed(3).checked_mul(fixed(4), Rounding::Down), Some(fixed(12)));
+        assert_eq!(fixed(3).checked_mul(Fixed::zero(), Rounding::Up), Some(Fixed::zero()));
+        assert_eq!(Fixed::one().checked_mul(Fixed::from_parts(7), Rounding::Down), Some(Fixed::from_parts(7)));
+
+        // 10^-18 * 0.5 lies halfway between zero and the smallest value
+        let tiny = Fixed::from_parts(1);
+        let half = Fixed::from_parts(ONE / 2);
+        assert_eq!(tiny.checked_mul(half, Rounding::Down), Some(Fixed::zero()));
+        assert_eq!(tiny.checked_mul(half, Rounding::Up), Some(tiny));
+        assert_eq!(tiny.checked_mul(half, Rounding::Nearest), Some(tiny));
+
+        let below_half = Fixed::from_parts(ONE / 2 - 1);
+        assert_eq!(tiny.checked_mul(below_half, Rounding::Nearest), Some(Fixed::zero()));
+    }
+
+    #[test]
+    fn multiplication_overflows_only_past_range() {
+        // the intermediate product of the inner values exceeds u128
+        let big = Fixed::from_integer(MAX / ONE).unwrap();
+        assert_eq!(big.checked_mul(Fixed::one(), Rounding::Down), Some(big));
+        assert_eq!(big.checked_mul(Fixed::from_parts(ONE / 2), Rounding::Down).map(|r| r.to_integer(Rounding::Down)),
+                   Some(MAX / ONE / 2));
+
+        assert_eq!(big.checked_mul(fixed(2), Rounding::Down), None);
+        assert_eq!(Fixed::from_parts(MAX).checked_mul(Fixed::from_parts(MAX), Rounding::Down), None);
+    }
+
+    #[test]
+    fn division_rounds_by_mode() {
+        assert_eq!(fixed(12).checked_div(fixed(4), Rounding::Down), Some(fixed(3)));
+        assert_eq!(fixed(1).checked_div(fixed(3), Rounding::Down), Fixed::from_rational(1, 3, Rounding::Down));
+        assert_eq!(fixed(1).checked_div(fixed(3), Rounding::Up), Fixed::from_rational(1, 3, Rounding::Up));
+        assert_eq!(fixed(2).checked_div(fixed(3), Rounding::Nearest), Fixed::from_rational(2, 3, Rounding::Nearest));
+
+        assert_eq!(fixed(1).checked_div(Fixed::zero(), Rounding::Down), None);
+        assert_eq!(Fixed::from_parts(MAX).checked_div(Fixed::from_parts(1), Rounding::Down), None);
+        assert_eq!(Fixed::from_parts(MAX).checked_div(Fixed::from_parts(MAX), Rounding::Down), Some(Fixed::one()));
+    }
+
+    #[test]
+    fn integers_scale_by_fixed() {
+        let rate = Fixed::from_rational(3, 2, Rounding::Down).unwrap();
+        assert_eq!(rate.checked_mul_int(1_000, Rounding::Down), Some(1_500));
+        assert_eq!(rate.checked_mul_int(3, Rounding::Down), Some(4));
+        assert_eq!(rate.checked_mul_int(3, Rounding::Up), Some(5));
+        assert_eq!(rate.checked_mul_int(3, Rounding::Nearest), Some(5));
+
+        assert_eq!(rate.checked_div_int(3_000, Rounding::Down), Some(2_000));
+        assert_eq!(rate.checked_div_int(1, Rounding::Down), Some(0));
+        assert_eq!(rate.checked_div_int(1, Rounding::Up), Some(1));
+        assert_eq!(rate.checked_div_int(1, Rounding::Nearest), Some(1));
+        assert_eq!(Fixed::zero().checked_div_int(1, Rounding::Down), None);
+
+        // balances anywhere in the u128 range are scaled without truncation
+        assert_eq!(Fixed::one().checked_mul_int(MAX, Rounding::Down), Some(MAX));
+        assert_eq!(Fixed::one().checked_div_int(MAX, Rounding::Down), Some(MAX));
+        assert_eq!(fixed(2).checked_mul_int(MAX, Rounding::Down), None);
+    }
+
+    #[test]
+    fn mul_div_matches_narrow_arithmetic() {
+        let samples = [0u128, 1, 2, 3, 7, 10, 999, ONE - 1, ONE, ONE + 1, u64::max_value() as u128];
+        for a in samples.iter() {
+            for b in samples.iter() {
+                for c in samples.iter().filter(|c| **c > 0) {
+                    let product = a * b;
+                    assert_eq!(mul_div(*a, *b, *c, Rounding::Down), Some(product / c));
+                    assert_eq!(mul_div(*a, *b, *c, Rounding::Up), Some((product + c - 1) / c));
+                    assert_eq!(mul_div(*a, *b, *c, Rounding::Nearest), Some((product + c / 2) / c));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mul_div_handles_wide_products() {
+        assert_eq!(mul_div(MAX, MAX, MAX, Rounding::Down), Some(MAX));
+        assert_eq!(mul_div(MAX, 2, 2, Rounding::Down), Some(MAX));
+        assert_eq!(mul_div(MAX, 3, 4, Rounding::Down), Some(MAX / 4 * 3 + 2));
+        assert_eq!(mul_div(MAX, 3, 4, Rounding::Up), Some(MAX / 4 * 3 + 3));
+        assert_eq!(mul_div(1 << 127, 4, 8, Rounding::Down), Some(1 << 126));
+
+        assert_eq!(mul_div(MAX, MAX, MAX - 1, Rounding::Down), None);
+        assert_eq!(mul_div(MAX, 2, 1, Rounding::Down), None);
+        assert_eq!(mul_div(MAX, MAX, 0, Rounding::Down), None);
+
+        // a maximal quotient cannot round up any further
+        assert_eq!(mul_div(MAX, 2, 2, Rounding::Up), Some(MAX));
+        assert_eq!(mul_div(MAX, MAX, MAX, Rounding::Up), Some(MAX));
+    }
+
+    #[test]
+    fn ordering_follows_value() {
+        assert!(Fixed::zero() < Fixed::from_parts(1));
+        assert!(Fixed::from_rational(1, 3, Rounding::Up).unwrap() > Fixed::from_rational(1, 3, Rounding::Down).unwrap());
+        assert_eq!(fixed(2).max(fixed(3)), fixed(3));
+        assert_eq!(fixed(2).min(fixed(3)), fixed(2));
+    }
+}
@@ -0,0 +1,71 @@
+use support::{ StorageMap, StorageValue };
+use parity_codec::Codec;
+use rstd::prelude::*;
+use rstd::marker::PhantomData;
+
+// set of items in storage that can be enumerated by index.
+// members are kept dense in `Items` at slots 0..Len, and the slot of
+// each member is kept in `Slots`. removing a member moves the last
+// member into the freed slot, so members may change slots, but every
+// member is always found at exactly one slot below the length
+pub struct IterableSet<Item, Items, Slots, Len>(PhantomData<(Item, Items, Slots, Len)>);
+
+impl<Item, Items, Slots, Len> IterableSet<Item, Items, Slots, Len> where
+    Item: Codec,
+    Items: StorageMap<u64, Item, Query = Option<Item>>,
+    Slots: StorageMap<Item, u64, Query = Option<u64>>,
+    Len: StorageValue<u64, Query = u64>,
+{
+    pub fn len() -> u64 {
+        Len::get()
+    }
+
+    pub fn contains(item: &Item) -> bool {
+        Slots::exists(item)
+    }
+
+    // add an item to the end of the set, unless it is already a member;
+    // `None` if the set is full
+    pub fn insert(item: &Item) -> Option<()> {
+        if Self::contains(item) {
+            return Some(());
+        }
+
+        let slot = Len::get();
+        let len = slot.checked_add(1)?;
+
+        Items::insert(slot, item);
+        Slots::insert(item, slot);
+        Len::put(len);
+
+        Some(())
+    }
+
+    // remove an item if it is a member, moving the last member into its slot
+    pub fn remove(item: &Item) {
+        let slot = match Slots::take(item) {
+            Some(slot) => slot,
+            None => return,
+        };
+
+        // the set holds at least the removed item
+        let last = Len::get().saturating_sub(1);
+        if slot != last {
+            if let Some(moved) = Items::get(last) {
+                Items::insert(slot, &moved);
+                Slots::insert(&moved, slot);
+            }
+        }
+
+        Items::remove(last);
+        Len::put(last);
+    }
+
+    // up to `limit` members from slot `start` on; pages are only
+    // consistent with each other if the set is unchanged between them
+    pub fn page(start: u64, limit: u64) -> Vec<Item> {
+        let end = start.saturating_add(limit).min(Self::len());
+
+        (start..end).filter_map(|slot| Items::get(slot)).collect()
+    }
+}
@@ -6,49 +6,622 @@ use support::{
     StorageMap,
     dispatch::Result, 
     ensure,
-    traits::Currency, 
-    traits::ReservableCurrency,
 };
-use system::{ ensure_signed };
+use system::{ ensure_signed, ensure_root };
 use parity_codec::{ Encode, Decode };
-use runtime_primitives::traits::{ As };
+use runtime_primitives::traits::{ As, Zero, CheckedAdd, CheckedSub, Saturating, Convert };
 use runtime_primitives::{ Perbill };
+use crate::fixed::{ Fixed, Rounding, mul_div };
+use crate::iterable_set::IterableSet;
+#[cfg(feature = "std")]
+use serde::{ Serialize, Deserialize };
+use rstd::prelude::*;
+use rstd::result;
+use rstd::marker::PhantomData;
+use rstd::borrow::Borrow;
+
+const BILLION: u64 = 1_000_000_000;
+
+// prices are quoted in the unit of account, scaled by one billion
+pub const PRICE_ONE: u128 = BILLION as u128;
+
+// rates are quoted per year of 365 days, and accrued by the
+// seconds elapsed according to the timestamp module
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+// identifier of the lending pool, from which its account is derived
+pub const MODULE_ID: [u8; 8] = *b"lendpool";
+
+// identifier of the vault holding posted collateral
+pub const COLLATERAL_ID: [u8; 8] = *b"lendvolt";
+
+// bounds on risk parameters set by governance, in billionths
+const MIN_CLOSE_FACTOR: u64 = BILLION / 20;
+const MAX_CLOSE_FACTOR: u64 = BILLION / 10 * 9;
+const MAX_LIQUIDATION_INCENTIVE: u64 = BILLION / 2;
+const MAX_PENALTY_RATE: u64 = BILLION / 2;
+
+// longest grace period governance may set, in seconds
+const MAX_GRACE_PERIOD: u64 = SECONDS_PER_YEAR;
+
+// layout of the module's storage, bumped with each change that
+// requires existing chains to be migrated in `Module::migrate`.
+// version 0 is the baseline runtime's single native market
+pub const STORAGE_VERSION: u32 = 1;
+
+// identifier of a position, allocated sequentially as positions open
+pub type PositionId = u64;
+
+// side of a market a position is on; an account may hold
+// one position of each kind in every market
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum PositionKind {
+    // shares held in the market's share asset
+    Supply,
+    // outstanding borrow, tracked by a Terms struct
+    Borrow,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Position<AccountId, AssetId> {
+    pub owner: AccountId,
+    pub asset: AssetId,
+    pub kind: PositionKind,
+}
 
 #[derive(Encode, Decode, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "std", derive(Debug))]
 pub struct Terms<Balance, BlockNumber> {
+    // principal as of the index snapshot below
+    balance: Balance,
+    // borrow index at the time the position was opened
+    index: Fixed,
+    start_block: BlockNumber,
+    reserved: Balance,
+}
+
+// supply or borrow of an account in the baseline runtime, keyed by
+// account in `UserBalance` and read once by `Module::migrate`
+#[derive(Encode, Decode)]
+struct BaselineTerms<Balance, BlockNumber> {
     deposit: bool,
+    // compounded every block at the rate below, which the
+    // markets' own rates supersede
     balance: Balance,
-    interest_rate: Perbill,
+    _interest_rate: Perbill,
     start_block: BlockNumber,
+    // the owner's whole reserved balance once the borrow was reserved
     reserved: Balance,
 }
 
-pub trait Trait: system::Trait + balances::Trait {
+// schedule of a fixed-term borrow, fixed when the borrow is taken
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct LoanTerm<Moment> {
+    // time the borrow is due in full
+    pub maturity: Moment,
+    // end of the grace period after maturity, from which
+    // the borrow defaults and accrues penalty interest
+    pub default_time: Moment,
+    // time penalty interest has been charged up to
+    pub penalty_time: Moment,
+}
+
+// risk and rate parameters of a market, one market per asset
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct Market<AssetId> {
+    // asset minted to suppliers as a transferable claim on the market
+    pub share_asset: AssetId,
+    // share of collateral in this market that may be borrowed against
+    pub collateral_factor: Perbill,
+    // share of borrow interest retained by the protocol as reserves
+    pub reserve_factor: Perbill,
+    // jump rate model parameters, used when the runtime selects `JumpRate`
+    // Borrow Rate = Base + min(U, Kink) * Multiplier
+    //                    + max(U - Kink, 0) * Jump Multiplier
+    pub base_rate: Perbill,
+    pub multiplier: Perbill,
+    pub jump_multiplier: Perbill,
+    pub kink: Perbill,
+}
+
+// an account's balances in one market, with interest accrued to the current block
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct AccountMarket<AssetId, Balance> {
+    pub asset: AssetId,
+    // underlying the account's shares redeem for
+    pub supplied: Balance,
+    pub shares: Balance,
+    pub borrowed: Balance,
+    pub collateral: Balance,
+    // maturity of a fixed-term borrow, in seconds, and whether it is
+    // unpaid past its grace period and so liquidatable in full
+    pub maturity: Option<u64>,
+    pub defaulted: bool,
+}
+
+// an account's balances across markets, returned by the lending runtime api
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct AccountSummary<AssetId, Balance> {
+    pub markets: Vec<AccountMarket<AssetId, Balance>>,
+    // borrowing capacity of collateral over outstanding borrows; below
+    // one the account may be liquidated, and it is `None` while the
+    // account has nothing borrowed
+    pub health_factor: Option<Fixed>,
+}
+
+// state of a market, with interest accrued to the current block
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct MarketSummary<Balance> {
+    pub total_supply: Balance,
+    pub total_borrow: Balance,
+    pub total_reserves: Balance,
+    pub utilization: Fixed,
+    pub supply_rate: Fixed,
+    pub borrow_rate: Fixed,
+    // underlying paid out per share
+    pub exchange_rate: Fixed,
+}
+
+// risk parameter changed by root, carried by the `ParameterUpdated` event
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum RiskParameter<AssetId, Balance> {
+    // market, base rate, multiplier, jump multiplier, kink
+    RateModel(AssetId, Perbill, Perbill, Perbill, Perbill),
+    CollateralFactor(AssetId, Perbill),
+    ReserveFactor(AssetId, Perbill),
+    LiquidationIncentive(Perbill),
+    CloseFactor(Perbill),
+    // market, cap, or `None` once lifted
+    SupplyCap(AssetId, Option<Balance>),
+    BorrowCap(AssetId, Option<Balance>),
+    // seconds after maturity before a fixed-term borrow defaults
+    GracePeriod(u64),
+    // annual rate charged on defaulted borrows on top of the market rate
+    PenaltyRate(Perbill),
+}
+
+// action in a market that the pause guardian may switch off;
+// repayments can never be paused
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum Action {
+    // deposits
+    Supply,
+    // borrows
+    Borrow,
+    // liquidations repaying or seizing in the market
+    Liquidate,
+    // withdrawals of supply and of collateral
+    Withdraw,
+}
+
+// reason a lending call failed. dispatch errors are plain strings at this
+// Substrate revision, so a failed call returns the variant's message; its
+// code, the variant's index in the SCALE encoding, is what clients should
+// match on. variants are only ever appended, so codes are stable across
+// runtime upgrades
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum LendingError {
+    MarketNotFound,
+    MarketExists,
+    ShareAssetInUse,
+    ZeroAmount,
+    DepositTooSmall,
+    // the account holds neither side of the market
+    NoPosition,
+    NoSupply,
+    NoBorrow,
+    NoCollateral,
+    ExceedsSupply,
+    ExceedsBorrow,
+    ExceedsCollateral,
+    ExceedsReserves,
+    ExceedsCloseFactor,
+    // the pool's unlent cash does not cover the amount
+    InsufficientLiquidity,
+    // the call would leave borrows above the borrowing capacity of collateral
+    Undercollateralized,
+    NotLiquidatable,
+    SelfLiquidation,
+    NoPrice,
+    StalePrice,
+    NoSuppliers,
+    // retired with flash loans, which this revision cannot revert
+    FlashLoanNotRepaid,
+    InvalidCollateralFactor,
+    InvalidReserveFactor,
+    InvalidKink,
+    InvalidJumpMultiplier,
+    RateTooHigh,
+    InvalidLiquidationIncentive,
+    InvalidCloseFactor,
+    // retired with flash loans
+    InvalidFlashLoanFee,
+    Overflow,
+    ActionPaused,
+    NotPauseGuardian,
+    SupplyCapExceeded,
+    BorrowCapExceeded,
+    // a fixed-term borrow is only opened on an account not yet borrowing
+    BorrowExists,
+    InvalidMaturity,
+    // a fixed-term borrow past its maturity cannot be added to
+    LoanMatured,
+    // the account cannot pay the amount from its free balance
+    InsufficientBalance,
+    InvalidGracePeriod,
+    InvalidPenaltyRate,
+    NoBaselineAccount,
+}
+
+impl LendingError {
+    // every error, in order of their codes
+    pub const ALL: [LendingError; 42] = [
+        LendingError::MarketNotFound,
+        LendingError::MarketExists,
+        LendingError::ShareAssetInUse,
+        LendingError::ZeroAmount,
+        LendingError::DepositTooSmall,
+        LendingError::NoPosition,
+        LendingError::NoSupply,
+        LendingError::NoBorrow,
+        LendingError::NoCollateral,
+        LendingError::ExceedsSupply,
+        LendingError::ExceedsBorrow,
+        LendingError::ExceedsCollateral,
+        LendingError::ExceedsReserves,
+        LendingError::ExceedsCloseFactor,
+        LendingError::InsufficientLiquidity,
+        LendingError::Undercollateralized,
+        LendingError::NotLiquidatable,
+        LendingError::SelfLiquidation,
+        LendingError::NoPrice,
+        LendingError::StalePrice,
+        LendingError::NoSuppliers,
+        LendingError::FlashLoanNotRepaid,
+        LendingError::InvalidCollateralFactor,
+        LendingError::InvalidReserveFactor,
+        LendingError::InvalidKink,
+        LendingError::InvalidJumpMultiplier,
+        LendingError::RateTooHigh,
+        LendingError::InvalidLiquidationIncentive,
+        LendingError::InvalidCloseFactor,
+        LendingError::InvalidFlashLoanFee,
+        LendingError::Overflow,
+        LendingError::ActionPaused,
+        LendingError::NotPauseGuardian,
+        LendingError::SupplyCapExceeded,
+        LendingError::BorrowCapExceeded,
+        LendingError::BorrowExists,
+        LendingError::InvalidMaturity,
+        LendingError::LoanMatured,
+        LendingError::InsufficientBalance,
+        LendingError::InvalidGracePeriod,
+        LendingError::InvalidPenaltyRate,
+        LendingError::NoBaselineAccount,
+    ];
+
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.get(code as usize).cloned()
+    }
+
+    // error a failed call returned, recovered from its message, including
+    // those of the assets and balances modules that lending calls into
+    pub fn from_message(message: &str) -> Option<Self> {
+        match message {
+            "Insufficient asset balance for transfer"
+            | "Insufficient asset balance to reserve"
+            | "Insufficient asset balance to burn"
+            | "Insufficient reserved asset balance to repatriate"
+            | "balance too low to send value"
+            | "not enough free funds"
+            | "account liquidity restrictions prevent withdrawal" => Some(LendingError::InsufficientBalance),
+            "Overflow encountered crediting asset transfer"
+            | "Overflow encountered reserving asset"
+            | "Overflow encountered repatriating asset"
+            | "Overflow encountered issuing asset"
+            | "destination balance too high to receive value" => Some(LendingError::Overflow),
+            _ => Self::ALL.iter().find(|error| error.message() == message).cloned(),
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            LendingError::MarketNotFound => "Market does not exist.",
+            LendingError::MarketExists => "Market already exists.",
+            LendingError::ShareAssetInUse => "Share asset is already in use.",
+            LendingError::ZeroAmount => "Amount must be non-zero.",
+            LendingError::DepositTooSmall => "Deposit is too small to mint shares.",
+            LendingError::NoPosition => "Account has no position in this market.",
+            LendingError::NoSupply => "Account has no supply in this market.",
+            LendingError::NoBorrow => "Account has no outstanding borrow in this market.",
+            LendingError::NoCollateral => "Account has no collateral in this market.",
+            LendingError::ExceedsSupply => "Withdrawal exceeds supplied balance.",
+            LendingError::ExceedsBorrow => "Repay amount exceeds outstanding borrow.",
+            LendingError::ExceedsCollateral => "Withdrawal exceeds posted collateral.",
+            LendingError::ExceedsReserves => "Amount exceeds market reserves.",
+            LendingError::ExceedsCloseFactor => "Repay amount exceeds close factor.",
+            LendingError::InsufficientLiquidity => "Insufficient market liquidity.",
+            LendingError::Undercollateralized => "Account would be under-collateralized.",
+            LendingError::NotLiquidatable => "Borrower is not under-collateralized.",
+            LendingError::SelfLiquidation => "Borrower cannot liquidate own position.",
+            LendingError::NoPrice => "No price available for asset.",
+            LendingError::StalePrice => "Price feed is stale.",
+            LendingError::NoSuppliers => "Market has no suppliers to reallocate reserves to.",
+            LendingError::FlashLoanNotRepaid => "Flash loan was not repaid with its fee.",
+            LendingError::InvalidCollateralFactor => "Collateral factor must be below 100%.",
+            LendingError::InvalidReserveFactor => "Reserve factor must be below 100%.",
+            LendingError::InvalidKink => "Kink must be between 0% and 100%.",
+            LendingError::InvalidJumpMultiplier => "Jump multiplier must not be below the multiplier.",
+            LendingError::RateTooHigh => "Borrow rate at full utilization must be below 100%.",
+            LendingError::InvalidLiquidationIncentive => "Liquidation incentive must not exceed 50%.",
+            LendingError::InvalidCloseFactor => "Close factor must be between 5% and 90%.",
+            LendingError::InvalidFlashLoanFee => "Flash loan fee must not exceed 1%.",
+            LendingError::Overflow => "Arithmetic overflow in lending.",
+            LendingError::ActionPaused => "Action is paused in this market.",
+            LendingError::NotPauseGuardian => "Sender is not the pause guardian.",
+            LendingError::SupplyCapExceeded => "Deposit would exceed the market's supply cap.",
+            LendingError::BorrowCapExceeded => "Borrow would exceed the market's borrow cap.",
+            LendingError::BorrowExists => "Account already borrows from this market.",
+            LendingError::InvalidMaturity => "Maturity must be in the future.",
+            LendingError::LoanMatured => "Fixed-term borrow has matured.",
+            LendingError::InsufficientBalance => "Account balance is too low.",
+            LendingError::InvalidGracePeriod => "Grace period must not exceed a year.",
+            LendingError::InvalidPenaltyRate => "Penalty rate must not exceed 50%.",
+            LendingError::NoBaselineAccount => "Account has no balance left from the baseline runtime.",
+        }
+    }
+}
+
+impl From<LendingError> for &'static str {
+    fn from(error: LendingError) -> &'static str {
+        error.message()
+    }
+}
+
+// rates returned by a model are annual, and may be
+// swapped per deployment through `Trait::InterestRateModel`
+pub trait InterestRateModel<AssetId> {
+    fn borrow_rate(asset: AssetId, cash: u128, borrows: u128, reserves: u128) -> Fixed;
+    fn supply_rate(asset: AssetId, cash: u128, borrows: u128, reserves: u128) -> Fixed;
+}
+
+// flat rates irrespective of market or utilization, as in the proof-of-concept
+pub struct FlatRate;
+
+impl<AssetId> InterestRateModel<AssetId> for FlatRate {
+    fn borrow_rate(_asset: AssetId, _cash: u128, _borrows: u128, _reserves: u128) -> Fixed {
+        Fixed::from_perbill(Perbill::from_percent(3))
+    }
+
+    fn supply_rate(_asset: AssetId, _cash: u128, _borrows: u128, _reserves: u128) -> Fixed {
+        Fixed::from_perbill(Perbill::from_percent(1))
+    }
+}
+
+// jump rate model: the borrow rate climbs gently up to the kink
+// (optimal utilization) and steeply beyond it, while suppliers
+// earn the borrow rate scaled down by utilization, less the
+// share of interest retained as reserves.
+// parameters are read from each market in the lending module's storage
+pub struct JumpRate<T>(PhantomData<T>);
+
+impl<T: Trait> InterestRateModel<T::AssetId> for JumpRate<T> {
+    fn borrow_rate(asset: T::AssetId, cash: u128, borrows: u128, reserves: u128) -> Fixed {
+        let market = <Module<T>>::market(asset);
+        let util = utilization(cash, borrows, reserves);
+        let kink = Fixed::from_perbill(market.kink);
+
+        let normal = fraction_of(util.min(kink), Fixed::from_perbill(market.multiplier));
+        let excess = fraction_of(util.saturating_sub(kink), Fixed::from_perbill(market.jump_multiplier));
+
+        Fixed::from_perbill(market.base_rate)
+            .saturating_add(normal)
+            .saturating_add(excess)
+            .min(Fixed::one())
+    }
+
+    fn supply_rate(asset: T::AssetId, cash: u128, borrows: u128, reserves: u128) -> Fixed {
+        let util = utilization(cash, borrows, reserves);
+        let borrow_rate = Self::borrow_rate(asset, cash, borrows, reserves);
+        let retained = Fixed::one()
+            .saturating_sub(Fixed::from_perbill(<Module<T>>::market(asset).reserve_factor));
+
+        fraction_of(fraction_of(borrow_rate, util), retained)
+    }
+}
+
+// source of asset prices in the unit of account, scaled by PRICE_ONE
+pub trait PriceFeed<AssetId> {
+    // latest known price, however old
+    fn price(asset: AssetId) -> Option<u128>;
+
+    // whether the price is recent enough to open new borrows against
+    fn is_fresh(asset: AssetId) -> bool;
+}
+
+// every asset priced at parity, for runtimes without an oracle
+impl<AssetId> PriceFeed<AssetId> for () {
+    fn price(_asset: AssetId) -> Option<u128> {
+        Some(PRICE_ONE)
+    }
+
+    fn is_fresh(_asset: AssetId) -> bool {
+        true
+    }
+}
+
+// Utilization Ratio = Borrows[a] / (Cash[a] + Borrows[a] - Reserves[a])
+pub fn utilization(cash: u128, borrows: u128, reserves: u128) -> Fixed {
+    let denominator = cash.saturating_add(borrows).saturating_sub(reserves);
+
+    if denominator == 0 {
+        return Fixed::zero();
+    }
+
+    // the quotient only overflows far above one
+    Fixed::from_rational(borrows, denominator, Rounding::Down)
+        .map_or(Fixed::one(), |util_ratio| util_ratio.min(Fixed::one()))
+}
+
+// product of two fractions of at most one, which cannot overflow
+fn fraction_of(value: Fixed, fraction: Fixed) -> Fixed {
+    value.checked_mul(fraction, Rounding::Down).unwrap_or_default()
+}
+
+// key of a map entry in `Lending <item>`, as laid out by decl_storage,
+// for reading entries whose type has changed since they were written
+fn legacy_key<K: Encode>(item: &[u8], key: &K) -> Vec<u8> {
+    let mut prefixed = b"Lending ".to_vec();
+    prefixed.extend_from_slice(item);
+    key.encode_to(&mut prefixed);
+    prefixed
+}
+
+// Perbill expressed in billionths
+fn parts(rate: Perbill) -> u64 {
+    rate * BILLION
+}
+
+// registry of open positions over `UserArray`, `UserIndex` and `UserCount`
+type OpenPositions<T> = IterableSet<PositionId, UserArray<T>, UserIndex<T>, UserCount<T>>;
+
+pub trait Trait: system::Trait + balances::Trait + assets::Trait + timestamp::Trait {
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	// model used to price borrowing and supplying
+	type InterestRateModel: InterestRateModel<Self::AssetId>;
+
+	// prices used to value collateral and borrows across markets
+	type PriceFeed: PriceFeed<Self::AssetId>;
+
+	// lossless conversion of balances to and from u128, in
+	// which interest and exchange rates are applied
+	type BalanceToU128: Convert<Self::Balance, u128> + Convert<u128, Self::Balance>;
+}
+
+// conversion for runtimes whose balances are u128
+pub struct U128Balance;
+
+impl Convert<u128, u128> for U128Balance {
+    fn convert(balance: u128) -> u128 {
+        balance
+    }
 }
 
 decl_storage! {
 	trait Store for Module<T: Trait> as Lending {
-                // liquidity provider set in genesis config
-                LiquidityProvider get(liquidity_provider) config(): T::AccountId;
+                // layout the storage below was written in; chains predating
+                // versioning read as zero, and new chains start current
+                StorageVersion get(storage_version) build(|_: &GenesisConfig<T>| STORAGE_VERSION): u32;
 
-                // Total Supply & Borrow
-                // **not yet implemented**
-                TotalSupply get(total_supply): u64;
-                TotalBorrow get(total_borrow): u64;
+                // external account that held the pool's funds before the pool
+                // account, cleared once its funds are migrated
+                LiquidityProvider get(liquidity_provider): Option<T::AccountId>;
 
-                // Utilization Ratio = Borrows[a] / (Cash[a] + Borrows[a])
-                // **not yet implemented**
-                UtilRatio get(util_ratio): Perbill;
+                // market registry keyed by the asset lent in each market,
+                // set in genesis config and extended by root
+                Markets get(market) config(markets): map T::AssetId => Market<T::AssetId>;
 
-                // mapping of AccountId to Terms struct
-                UserBalance get(user_balance): map T::AccountId => Terms<T::Balance, T::BlockNumber>;
+                // every listed market, in the order they were added
+                MarketIds get(market_ids) build(|config: &GenesisConfig<T>| {
+                    config.markets.iter().map(|(asset, _)| *asset).collect::<Vec<_>>()
+                }): Vec<T::AssetId>;
 
-                // rumtime special purposed array
-                UserArray get(user_array): map u64 => T::AccountId;
+                // market whose supply each share asset represents
+                ShareMarket get(share_market) build(|config: &GenesisConfig<T>| {
+                    config.markets.iter()
+                        .map(|(asset, market)| (market.share_asset, *asset))
+                        .collect::<Vec<_>>()
+                }): map T::AssetId => T::AssetId;
+
+                // Total Supply & Borrow of each market, including accrued interest
+                TotalSupply get(total_supply): map T::AssetId => T::Balance;
+                TotalBorrow get(total_borrow): map T::AssetId => T::Balance;
+
+                // borrow interest retained by the protocol in each market,
+                // held in the pool alongside supplied liquidity
+                TotalReserves get(total_reserves): map T::AssetId => T::Balance;
+
+                // Utilization Ratio = Borrows[a] / (Cash[a] + Borrows[a] - Reserves[a])
+                UtilRatio get(util_ratio): map T::AssetId => Fixed;
+
+                // annual rates of each market, recomputed
+                // on each change to supply or borrows
+                SupplyRate get(supply_rate): map T::AssetId => Fixed;
+                BorrowRate get(borrow_rate): map T::AssetId => Fixed;
+
+                // Interest Rate Index, updated lazily upon any extrinsic
+                // Index[a,t] = Index[a,t-1] * (1 + r * Δt / year)
+                SupplyIndex get(supply_index): map T::AssetId => Fixed = Fixed::one();
+                BorrowIndex get(borrow_index): map T::AssetId => Fixed = Fixed::one();
+
+                // time interest was last accrued in each market; a market
+                // starts accruing from the first extrinsic to touch it
+                AccrualTime get(accrual_time): map T::AssetId => Option<T::Moment>;
+
+                // share of an unhealthy borrow that may be repaid in a
+                // single liquidation, and the bonus on seized collateral
+                CloseFactor get(close_factor) config(): Perbill;
+                LiquidationIncentive get(liquidation_incentive) config(): Perbill;
+
+                // seconds after maturity before an unpaid fixed-term borrow
+                // defaults, and the annual rate it is charged from then on
+                // top of the market rate, retained as reserves
+                GracePeriod get(grace_period) config(): u64;
+                PenaltyRate get(penalty_rate) config(): Perbill;
+
+                // account set by root that may pause actions in any
+                // market, which only root may resume
+                PauseGuardian get(pause_guardian): Option<T::AccountId>;
+
+                // limits on the total supply and total borrow of each market,
+                // bounding exposure to new or thin assets; uncapped if unset
+                SupplyCap get(supply_cap): map T::AssetId => Option<T::Balance>;
+                BorrowCap get(borrow_cap): map T::AssetId => Option<T::Balance>;
+
+                // actions currently paused in each market
+                Paused get(is_paused): map (T::AssetId, Action) => bool;
+
+                // collateral posted in each market, held in the collateral vault
+                // so that lending only ever releases what it holds itself
+                VaultedCollateral get(vaulted_collateral): map (T::AssetId, T::AccountId) => T::Balance;
+
+                // markets in which an account has posted collateral or
+                // holds a position, walked when valuing the account
+                AccountMarkets get(account_markets): map T::AccountId => Vec<T::AssetId>;
+
+                // open positions by id, and the id of each account's
+                // position on either side of a market
+                Positions get(position): map PositionId => Option<Position<T::AccountId, T::AssetId>>;
+                PositionIds get(position_id): map (T::AssetId, T::AccountId, PositionKind) => Option<PositionId>;
+                AccountPositions get(account_positions): map T::AccountId => Vec<PositionId>;
+                NextPositionId get(next_position_id): PositionId;
+
+                // mapping of borrow position to its Terms struct
+                UserBalance get(user_balance): map PositionId => Terms<T::Balance, T::BlockNumber>;
+
+                // schedule of each fixed-term borrow, by borrow position
+                LoanTerms get(loan_term): map PositionId => Option<LoanTerm<T::Moment>>;
+
+                // every open position, enumerable by slot for indexers and
+                // keepers; kept as an `OpenPositions` set
+                UserArray get(user_array): map u64 => Option<PositionId>;
                 UserCount get(user_count): u64;
-                UserIndex: map T::AccountId => u64;
+                UserIndex get(user_index): map PositionId => Option<u64>;
 	}
 }
 
@@ -57,495 +630,3489 @@ decl_module! {
 		// Initializing events
 		fn deposit_event<T>() = default;
 
-                fn deposit(_origin, deposit_value: T::Balance) -> Result {
+                fn on_initialize(_n: T::BlockNumber) {
+                    // storage written by an earlier runtime is upgraded
+                    // once, in the first block after the upgrade
+                    if Self::storage_version() < STORAGE_VERSION {
+                        Self::migrate();
+                    }
+
+                    // chains predating the pool account hand the funds held
+                    // by their liquidity provider over once
+                    if let Some(provider) = <LiquidityProvider<T>>::take() {
+                        Self::migrate_liquidity_provider(provider);
+                    }
+                }
+
+                fn deposit(_origin, asset: T::AssetId, deposit_value: T::Balance) -> Result {
                     let sender = ensure_signed(_origin)?;
+                    Self::reporting_failure(sender.clone(), move || {
+                        ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+                        Self::ensure_active(asset, Action::Supply)?;
 
-                    // user cannot deposit more to account, can only withdraw
-                    ensure!(!<UserBalance<T>>::exists(&sender), 
-                            "User has an existing deposit.");
+                        // bring the supply index up to date
+                        Self::accrue_interest(asset)?;
 
-                    // interest rate hard-coded at 1 percent
-                    let interest_rate = Perbill::from_percent(1);
+                        // shares are minted at the current exchange rate
+                        let shares = Self::shares_for(deposit_value, Self::supply_index(asset), Rounding::Down)?;
+                        ensure!(!shares.is_zero(), LendingError::DepositTooSmall.into());
 
-                    // set user supply terms
-                    let user_terms = Terms {
-                        deposit: true,
-                        balance: deposit_value,
-                        interest_rate: interest_rate,
-                        start_block: <system::Module<T>>::block_number(),
-                        reserved: <balances::Module<T>>::reserved_balance(&sender),
-                    };
+                        let incr_total_supply = Self::total_supply(asset)
+                            .checked_add(&deposit_value)
+                            .ok_or(LendingError::Overflow)?;
+                        ensure!(Self::supply_cap(asset).map_or(true, |cap| incr_total_supply <= cap),
+                                LendingError::SupplyCapExceeded.into());
 
-                    let incr_total_supply = Self::total_supply()
-                        .checked_add(<T::Balance as As<u64>>::as_(deposit_value))
-                        .ok_or("Overflow encourtered incrementing total supply")?;
+                        // funds are taken before anything is written, as
+                        // nothing written is undone should the transfer fail
+                        Self::transfer_funds(
+                            asset,
+                            sender.clone(),
+                            Self::pool_account(),
+                            deposit_value,
+                        )?;
 
-                    // update TotalSupply to new value
-                    <TotalSupply<T>>::put(incr_total_supply);
+                        // update TotalSupply to new value
+                        <TotalSupply<T>>::insert(asset, incr_total_supply);
+                        Self::update_rates(asset);
 
-                    // insert user supply terms to storage
-                    <UserBalance<T>>::insert(&sender, user_terms);
+                        // a top-up adds to the account's existing supply position
+                        Self::open_position(asset, &sender, PositionKind::Supply)?;
+                        Self::enter_market(asset, &sender);
 
-                    // increment chain specific array
-                    Self::increment_array(sender.clone())?;
+                        <assets::Module<T>>::issue(Self::market(asset).share_asset, &sender, shares)?;
 
-                    // transfer currency to liquidity provider
-                    Self::transfer_funds(
-                        sender.clone(),
-                        Self::liquidity_provider(),
-                        deposit_value,
-                    )?;
+                        // deposit 'CurrencySupplied' event
+                        Self::deposit_event(RawEvent::CurrencySupplied(asset, sender, deposit_value, shares));
 
-                    // deposit 'CurrencySupplied' event
-                    Self::deposit_event(RawEvent::CurrencySupplied(sender, deposit_value));
+                        Ok(())
+                    })
+                }
 
-                    Ok(())
+                fn withdraw_in_full(_origin, asset: T::AssetId) -> Result {
+                    let sender = ensure_signed(_origin)?;
+                    Self::reporting_failure(sender.clone(), move || {
+                        ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+                        Self::ensure_active(asset, Action::Withdraw)?;
+
+                        // check to make sure user has supplied to the market
+                        let shares = Self::shares_of(asset, &sender);
+                        if shares.is_zero() {
+                            ensure!(Self::borrow_position(asset, &sender).is_some(), 
+                                    LendingError::NoPosition.into());
+                            return Err(LendingError::NoSupply.into());
+                        }
+
+                        Self::accrue_interest(asset)?;
+
+                        // shares are redeemed at the current exchange rate
+                        let outgoing_balance = Self::supply_balance(asset, &sender)?;
+                        Self::redeem(asset, &sender, shares, outgoing_balance)
+                    })
                 }
 
-                fn withdraw_in_full(_origin) -> Result {
+                fn withdraw(_origin, asset: T::AssetId, withdraw_value: T::Balance) -> Result {
                     let sender = ensure_signed(_origin)?;
-                    
-                    // check to make sure user has an account
-                    ensure!(<UserBalance<T>>::exists(&sender), 
-                            "User does not have an existing account.");
+                    Self::reporting_failure(sender.clone(), move || {
+                        ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+                        ensure!(!withdraw_value.is_zero(), LendingError::ZeroAmount.into());
+                        Self::ensure_active(asset, Action::Withdraw)?;
+
+                        Self::accrue_interest(asset)?;
 
-                    // retrieve user_data struct from storage
-                    let mut user_data = Self::user_balance(&sender);
-                    ensure!(user_data.deposit == true, 
-                            "User has no supplied currency.");
+                        // shares burned are rounded up so that a withdrawal
+                        // never pays out more than the shares are worth
+                        let shares = Self::shares_for(withdraw_value, Self::supply_index(asset), Rounding::Up)?;
 
-                    // store balance for transfer later
-                    let outgoing_balance = user_data.balance;
+                        ensure!(shares <= Self::shares_of(asset, &sender),
+                                LendingError::ExceedsSupply.into());
 
-                    // set user balance to zero
-                    user_data.balance = <T::Balance as As<u64>>::sa(0);
+                        Self::redeem(asset, &sender, shares, withdraw_value)
+                    })
+                }
 
-                    // update struct in storage
-                    <UserBalance<T>>::insert(&sender, user_data);
+                fn borrow(_origin, asset: T::AssetId, borrow_value: T::Balance) -> Result {
+                    let sender = ensure_signed(_origin)?;
+                    Self::reporting_failure(sender.clone(), move || {
+                        // a fixed-term borrow may be added to under its schedule
+                        // until it matures
+                        if let Some(term) = Self::borrow_position(asset, &sender).and_then(Self::loan_term) {
+                            ensure!(<timestamp::Module<T>>::now() < term.maturity,
+                                    LendingError::LoanMatured.into());
+                        }
 
-                    // transfer money from liquidity provider to sender
-                    Self::transfer_funds(
-                        Self::liquidity_provider(),
-                        sender.clone(),
-                        outgoing_balance,
-                    )?;
+                        Self::borrow_funds(asset, &sender, borrow_value)
+                    })
+                }
+
+                // borrow due in full at `maturity`. unpaid past the grace period,
+                // it defaults, may be liquidated whatever the borrower's health,
+                // and is charged the penalty rate on top of the market rate
+                fn borrow_fixed_term(_origin, asset: T::AssetId, borrow_value: T::Balance, maturity: T::Moment) -> Result {
+                    let sender = ensure_signed(_origin)?;
+                    Self::reporting_failure(sender.clone(), move || {
+                        ensure!(Self::borrow_position(asset, &sender).is_none(),
+                                LendingError::BorrowExists.into());
+                        ensure!(maturity > <timestamp::Module<T>>::now(),
+                                LendingError::InvalidMaturity.into());
 
-                    // decrement array, promoting code cleanliness
-                    Self::decrement_array(sender.clone())?;
+                        let default_time = <T::Moment as As<u64>>::as_(maturity.clone())
+                            .checked_add(Self::grace_period())
+                            .map(<T::Moment as As<u64>>::sa)
+                            .ok_or(LendingError::Overflow)?;
 
-                    // deposit 'SupplyWithdrawn' event
-                    Self::deposit_event(RawEvent::SupplyWithdrawn(sender, outgoing_balance));
+                        Self::borrow_funds(asset, &sender, borrow_value)?;
 
-                    Ok(())
+                        let id = Self::borrow_position(asset, &sender)
+                            .ok_or(LendingError::NoBorrow)?;
+                        <LoanTerms<T>>::insert(id, LoanTerm {
+                            maturity: maturity.clone(),
+                            default_time: default_time.clone(),
+                            penalty_time: default_time,
+                        });
+
+                        Self::deposit_event(RawEvent::TermLoanOpened(id, sender, asset, maturity));
+
+                        Ok(())
+                    })
                 }
 
-                fn borrow(_origin, borrow_value: T::Balance) -> Result {
+                fn repay_in_full(_origin, asset: T::AssetId) -> Result {
                     let sender = ensure_signed(_origin)?;
+                    Self::reporting_failure(sender.clone(), move || {
+                        // check to make sure user has an account
+                        let id = Self::borrow_position(asset, &sender)
+                            .ok_or(LendingError::NoPosition)?;
 
-                    // user cannot borrow more, this is a one shot loan
-                    ensure!(!<UserBalance<T>>::exists(&sender), 
-                            "User has an existing loan.");
+                        Self::accrue_interest(asset)?;
 
-                    // high interest rate for borrowers, hard-coded
-                    let borrow_interest_rate = Perbill::from_percent(3);
+                        // store balance, including accrued interest, for transfer later
+                        // collateral stays posted until withdrawn with 'withdraw_collateral'
+                        let outgoing_balance = Self::balance_of(id, asset)?;
+                        Self::repay_borrow(asset, &sender, &sender, outgoing_balance)?;
 
-                    let incr_total_borrow = Self::total_borrow()
-                        .checked_add(<T::Balance as As<u64>>::as_(borrow_value))
-                        .ok_or("Overflow encourtered incrementing total borrow")?;
+                        Self::deposit_event(RawEvent::BorrowRepaid(asset, sender, outgoing_balance));
 
-                    <balances::Module<T>>::reserve(
-                        &sender,
-                        borrow_value,
-                    )?;
+                        Ok(())
 
-                    // Update TotalSupply to new value
-                    <TotalBorrow<T>>::put(incr_total_borrow);
+                    })
+                }
 
-                    // create Terms struct for user
-                    let user_data = Terms {
-                        deposit: false,
-                        balance: borrow_value,
-                        interest_rate: borrow_interest_rate,
-                        start_block: <system::Module<T>>::block_number(),
-                        reserved: <balances::Module<T>>::reserved_balance(&sender),
-                    };
+                fn repay(_origin, asset: T::AssetId, repay_value: T::Balance) -> Result {
+                    let sender = ensure_signed(_origin)?;
+                    Self::reporting_failure(sender.clone(), move || {
+                        let id = Self::borrow_position(asset, &sender)
+                            .ok_or(LendingError::NoPosition)?;
+                        ensure!(!repay_value.is_zero(), LendingError::ZeroAmount.into());
 
-                    // add struct to storage
-                    <UserBalance<T>>::insert(&sender, &user_data);
+                        Self::accrue_interest(asset)?;
 
-                    // increment chain specific array
-                    Self::increment_array(sender.clone())?;
+                        ensure!(repay_value <= Self::balance_of(id, asset)?,
+                                LendingError::ExceedsBorrow.into());
 
-                    // perform transfer of funds
-                    Self::transfer_funds(
-                        Self::liquidity_provider(),
-                        sender.clone(),
-                        borrow_value,
-                    )?;
+                        Self::repay_borrow(asset, &sender, &sender, repay_value)?;
 
-                    Self::deposit_event(RawEvent::CurrencyBorrowed(sender, borrow_value));
+                        Self::deposit_event(RawEvent::BorrowRepaid(asset, sender, repay_value));
 
-                    Ok(())
+                        Ok(())
+                    })
                 }
 
-                fn repay_in_full(_origin) -> Result {
+                fn deposit_collateral(_origin, asset: T::AssetId, collateral_value: T::Balance) -> Result {
                     let sender = ensure_signed(_origin)?;
+                    Self::reporting_failure(sender.clone(), move || {
+                        let position = (asset, sender.clone());
 
-                    // check to make sure user has an account
-                    ensure!(<UserBalance<T>>::exists(&sender), 
-                            "User does not have an existing account.");
+                        ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+                        ensure!(!collateral_value.is_zero(), LendingError::ZeroAmount.into());
 
-                    // retrieve user_data struct from storage
-                    let mut user_data = Self::user_balance(&sender);
+                        let incr_collateral = Self::vaulted_collateral(&position)
+                            .checked_add(&collateral_value)
+                            .ok_or(LendingError::Overflow)?;
 
-                    // check to ensure user has borrowed funds
-                    ensure!(user_data.deposit == false, "user has not borrowed funds");
+                        Self::transfer_funds(
+                            asset,
+                            sender.clone(),
+                            Self::collateral_account(),
+                            collateral_value,
+                        )?;
 
-                    // store balance for transfer later
-                    let outgoing_balance = user_data.balance;
+                        Self::set_vaulted_collateral(&position, incr_collateral);
+                        Self::enter_market(asset, &sender);
 
-                    // set user balance to zero
-                    user_data.balance = <T::Balance as As<u64>>::sa(0);
+                        Self::deposit_event(RawEvent::CollateralDeposited(asset, sender, collateral_value));
 
-                    // unreserve sender's currency
-                    <balances::Module<T>>::unreserve(
-                        &sender, 
-                        <balances::Module<T>>::reserved_balance(&sender),
-                    );
+                        Ok(())
+                    })
+                }
+
+                fn withdraw_collateral(_origin, asset: T::AssetId, collateral_value: T::Balance) -> Result {
+                    let sender = ensure_signed(_origin)?;
+                    Self::reporting_failure(sender.clone(), move || {
+                        let position = (asset, sender.clone());
 
-                    user_data.reserved = <balances::Module<T>>::reserved_balance(&sender);
+                        ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+                        ensure!(!collateral_value.is_zero(), LendingError::ZeroAmount.into());
+                        Self::ensure_active(asset, Action::Withdraw)?;
 
-                    // update struct in storage to reflect paid in full
-                    <UserBalance<T>>::insert(&sender, user_data);
+                        let decr_collateral = Self::vaulted_collateral(&position)
+                            .checked_sub(&collateral_value)
+                            .ok_or(LendingError::ExceedsCollateral)?;
 
-                    Self::decrement_array(sender.clone())?;
+                        // remaining collateral must still cover any outstanding borrows,
+                        // valued at fresh prices as new borrows are
+                        let (capacity, debt) = Self::account_liquidity(&sender)?;
+                        if debt > 0 {
+                            ensure!(Self::account_markets(&sender).into_iter().all(T::PriceFeed::is_fresh),
+                                    LendingError::StalePrice.into());
 
-                    // perform transfer of funds
-                    Self::transfer_funds(
-                        sender.clone(), 
-                        Self::liquidity_provider(),
-                        outgoing_balance,
-                    )?;
+                            let withdrawn_capacity = Self::apply_factor(
+                                Self::market(asset).collateral_factor,
+                                Self::value_of(collateral_value, Self::price_of(asset)?)?,
+                            );
+                            ensure!(debt <= capacity.saturating_sub(withdrawn_capacity),
+                                    LendingError::Undercollateralized.into());
+                        }
 
-                    Self::deposit_event(RawEvent::BorrowRepaid(sender, outgoing_balance));
+                        Self::transfer_funds(
+                            asset,
+                            Self::collateral_account(),
+                            sender.clone(),
+                            collateral_value,
+                        )?;
 
-                    Ok(())
+                        Self::set_vaulted_collateral(&position, decr_collateral);
+                        Self::exit_market_if_empty(asset, &sender);
 
+                        Self::deposit_event(RawEvent::CollateralWithdrawn(asset, sender, collateral_value));
+
+                        Ok(())
+                    })
                 }
 
-                fn on_finalize() {
-                    // existing only for the proof-of-concept
-                    // in future, this will be replaced with
-                    // an "Interest Rate Index" that gets updated
-                    // upon any extrinsic to the runtime
-                    // Index[a,n] = Index[a,n-1] * (1 + r * t)
-                    
-                    // this is computationally heavy, and 
-                    // not good practice for blockchains
+                fn liquidate(
+                    _origin,
+                    borrower: T::AccountId,
+                    borrow_asset: T::AssetId,
+                    collateral_asset: T::AssetId,
+                    repay_amount: T::Balance
+                ) -> Result {
+                    let liquidator = ensure_signed(_origin)?;
+                    Self::reporting_failure(liquidator.clone(), move || {
+                        let collateral_position = (collateral_asset, borrower.clone());
 
-                    // retrieve user count to iterate over
-                    let user_count = Self::user_count();
+                        ensure!(liquidator != borrower, LendingError::SelfLiquidation.into());
+                        Self::ensure_active(borrow_asset, Action::Liquidate)?;
+                        Self::ensure_active(collateral_asset, Action::Liquidate)?;
 
-                    // iterate over open accounts
-                    for each in 0..user_count {
-                        // retrieve address
-                        let addr = Self::user_array(each);
-                        // compound interest of each account
-                        Self::compound_interest(addr);
-                    }
+                        // only accounts with an outstanding borrow can be liquidated
+                        let id = Self::borrow_position(borrow_asset, &borrower)
+                            .ok_or(LendingError::NoBorrow)?;
+
+                        // a defaulted fixed-term borrow is liquidatable whatever
+                        // the borrower's health, and is due in full
+                        let defaulted = Self::is_defaulted(id);
+                        let (capacity, debt) = Self::account_liquidity(&borrower)?;
+                        ensure!(defaulted || capacity < debt, LendingError::NotLiquidatable.into());
+
+                        let borrow_balance = Self::borrow_balance(borrow_asset, &borrower)?;
+                        let max_repay = if defaulted {
+                            borrow_balance
+                        } else {
+                            Self::apply_factor_to(Self::close_factor(), borrow_balance)
+                        };
+                        ensure!(!repay_amount.is_zero(), LendingError::ZeroAmount.into());
+                        ensure!(repay_amount <= max_repay, LendingError::ExceedsCloseFactor.into());
+
+                        let collateral = Self::collateral(&collateral_position);
+                        ensure!(!collateral.is_zero(), LendingError::NoCollateral.into());
+
+                        // liquidator seizes collateral worth the repaid amount plus
+                        // the incentive, capped at what the borrower has posted
+                        let repay_worth = Self::value_of(repay_amount, Self::price_of(borrow_asset)?)?;
+                        let seize_worth = repay_worth.saturating_add(
+                            Self::apply_factor(Self::liquidation_incentive(), repay_worth)
+                        );
+                        let seize_amount = Self::amount_of(seize_worth, Self::price_of(collateral_asset)?)?
+                            .min(collateral);
+
+                        Self::accrue_interest(borrow_asset)?;
+
+                        // liquidator repays on behalf of the borrower, before
+                        // any collateral is moved
+                        Self::repay_borrow(borrow_asset, &liquidator, &borrower, repay_amount)?;
+
+                        // seized collateral is paid out of the vault to the liquidator,
+                        // and only then taken off the borrower's posted collateral
+                        Self::transfer_funds(
+                            collateral_asset,
+                            Self::collateral_account(),
+                            liquidator.clone(),
+                            seize_amount,
+                        )?;
+                        Self::set_vaulted_collateral(&collateral_position, collateral - seize_amount);
+
+                        Self::deposit_event(RawEvent::Liquidated(
+                            liquidator,
+                            borrower,
+                            borrow_asset,
+                            repay_amount,
+                            collateral_asset,
+                            seize_amount,
+                        ));
+
+                        Ok(())
+                    })
                 }
-	}
-}
 
-impl<T: Trait> Module<T> {
-    // **below function not yet implemented / used**
-    fn calculate_util_ratio(total_supply: u64, total_borrow: u64) -> Result {
-        let mkt_liquidity = total_supply - total_borrow;
-        let denominator = mkt_liquidity + total_borrow;
-        let util_ratio: f64 = (total_borrow / denominator) as f64;
-        // could not get the below to work
-        // the below fails
-        // <UtilRatio<T>>::put(Perbill::from_fraction(util_ratio));
-        // the below compiles
-        <UtilRatio<T>>::put(Perbill::from_percent(15));
-        
-        Ok(())
-    }
+                fn add_market(origin, asset: T::AssetId, market: Market<T::AssetId>) -> Result {
+                    ensure_root(origin)?;
 
-    fn compound_interest(account_to_compound: T::AccountId) -> Result {
-        let mut user_data = Self::user_balance(&account_to_compound);
-        let user_balance = user_data.balance;
-        let user_interest = user_data.interest_rate;
+                    ensure!(!<Markets<T>>::exists(&asset), LendingError::MarketExists.into());
+                    Self::validate_collateral_factor(market.collateral_factor)?;
+                    Self::validate_reserve_factor(market.reserve_factor)?;
+                    Self::validate_rate_model(&market)?;
 
-        // retrieve & update accrued interest
-        let accrued = user_interest * <T::Balance as As<u64>>::as_(user_balance);
-        let new_balance = <T::Balance as As<u64>>::as_(user_balance) + &accrued;
+                    // the share asset must be a fresh asset dedicated to this market
+                    let share_asset = market.share_asset;
+                    ensure!(!share_asset.is_zero() && share_asset != asset
+                            && !<Markets<T>>::exists(&share_asset)
+                            && !<ShareMarket<T>>::exists(&share_asset)
+                            && <assets::Module<T>>::total_issuance(share_asset).is_zero(),
+                            LendingError::ShareAssetInUse.into());
 
-        // update terms struct to reflect updated balance
-        user_data.balance = <T::Balance as As<u64>>::sa(new_balance);
+                    <ShareMarket<T>>::insert(share_asset, asset);
+                    <Markets<T>>::insert(asset, market);
+                    <MarketIds<T>>::mutate(|markets| markets.push(asset));
+                    <AccrualTime<T>>::insert(asset, <timestamp::Module<T>>::now());
+                    Self::update_rates(asset);
 
-        // update storage to reflect compounded interest
-        <UserBalance<T>>::insert(&account_to_compound, user_data);
+                    Self::deposit_event(RawEvent::MarketAdded(asset));
 
-        Ok(())
-    }
+                    Ok(())
+                }
 
-    fn transfer_funds(
-        outgoing: T::AccountId, 
-        incoming: T::AccountId,
-        transfer_value: T::Balance
-    ) -> Result {
-        // while it generally takes up the same amount of space,
-        // by moving to own function more logic can be added
-        // to the transfer later, if needed 
-        <balances::Module<T> as Currency<_>>::transfer(
-            &outgoing,
-            &incoming,
-            transfer_value,
-        )?;
+                fn set_collateral_factor(origin, asset: T::AssetId, collateral_factor: Perbill) -> Result {
+                    ensure_root(origin)?;
 
-        Ok(())
-    }
+                    ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+                    Self::validate_collateral_factor(collateral_factor)?;
 
-    fn increment_array(user_to_add: T::AccountId) -> Result {
-        // retrieve current user count for rumtime-purposed array
-        let user_count = Self::user_count();
-        // check for overflows
-        let new_user_count = user_count.checked_add(1)
-            .ok_or("Overflow adding a new user to total users")?;
+                    <Markets<T>>::mutate(asset, |market| market.collateral_factor = collateral_factor);
 
-        <UserArray<T>>::insert(user_count, &user_to_add);
-        <UserCount<T>>::put(new_user_count);
-        <UserIndex<T>>::insert(&user_to_add, user_count);
+                    Self::deposit_event(RawEvent::ParameterUpdated(
+                        RiskParameter::CollateralFactor(asset, collateral_factor)
+                    ));
 
-        Ok(())
-    }
+                    Ok(())
+                }
 
-    fn decrement_array(user_to_remove: T::AccountId) -> Result {
-        // retrieve current user count for runtime-purposed array
-        let user_count = Self::user_count();
-        let new_user_count = user_count.checked_sub(1)
-            .ok_or("Underflow subtracting a new user from total users")?;
+                fn set_rate_model(
+                    origin,
+                    asset: T::AssetId,
+                    base_rate: Perbill,
+                    multiplier: Perbill,
+                    jump_multiplier: Perbill,
+                    kink: Perbill
+                ) -> Result {
+                    ensure_root(origin)?;
 
-        let user_index = <UserIndex<T>>::get(&user_to_remove);
+                    ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
 
-        // if sender is not the last item in the list
-        if user_index != user_count {
-            // set last_user as the last user in the list
-            let last_user = <UserArray<T>>::get(user_count);
-            // swap and pop method
-            <UserArray<T>>::insert(&user_count, &last_user);
-            <UserIndex<T>>::insert(&last_user, user_index);
-        }
-        <UserArray<T>>::remove(&user_count);
-        <UserIndex<T>>::remove(&user_to_remove);
-        <UserCount<T>>::put(new_user_count);
-        <UserBalance<T>>::remove(user_to_remove);
+                    let mut market = Self::market(asset);
+                    market.base_rate = base_rate;
+                    market.multiplier = multiplier;
+                    market.jump_multiplier = jump_multiplier;
+                    market.kink = kink;
+                    Self::validate_rate_model(&market)?;
 
-        Ok(())
-    }
-}
+                    // interest up to now accrues at the old rates
+                    Self::accrue_interest(asset)?;
 
-decl_event!(
-	pub enum Event<T> 
-        where 
-            <T as system::Trait>::AccountId,
-            <T as balances::Trait>::Balance,
-        {
-                CurrencySupplied(AccountId, Balance),
-                CurrencyBorrowed(AccountId, Balance),
-                SupplyWithdrawn(AccountId, Balance),
-                BorrowRepaid(AccountId, Balance),
-	}
-);
+                    <Markets<T>>::insert(asset, market);
+                    Self::update_rates(asset);
 
-/// tests for this module
-#[cfg(test)]
-mod tests {
-	use super::*;
+                    Self::deposit_event(RawEvent::ParameterUpdated(
+                        RiskParameter::RateModel(asset, base_rate, multiplier, jump_multiplier, kink)
+                    ));
 
-	use runtime_io::with_externalities;
-	use primitives::{H256, Blake2Hasher};
-	use support::{ 
-            impl_outer_origin, 
-            assert_ok, 
-            assert_noop 
-        };
-	use runtime_primitives::{
-		BuildStorage,
-		traits::{BlakeTwo256, IdentityLookup},
-		testing::{Digest, DigestItem, Header}
-	};
+                    Ok(())
+                }
 
-	impl_outer_origin! {
-		pub enum Origin for Test {}
-	}
+                fn set_reserve_factor(origin, asset: T::AssetId, reserve_factor: Perbill) -> Result {
+                    ensure_root(origin)?;
 
-	#[derive(Clone, Eq, PartialEq)]
-	pub struct Test;
+                    ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+                    Self::validate_reserve_factor(reserve_factor)?;
 
-	impl system::Trait for Test {
-		type Origin = Origin;
-		type Index = u64;
-		type BlockNumber = u64;
-		type Hash = H256;
-		type Hashing = BlakeTwo256;
-		type Digest = Digest;
-		type AccountId = u64;
-		type Lookup = IdentityLookup<Self::AccountId>;
-		type Header = Header;
-		type Event = ();
-		type Log = DigestItem;
-	}
+                    // interest up to now is split at the old factor
+                    Self::accrue_interest(asset)?;
 
-	impl super::Trait for Test {
-		type Event = ();
-        }
+                    <Markets<T>>::mutate(asset, |market| market.reserve_factor = reserve_factor);
+                    Self::update_rates(asset);
 
-        impl balances::Trait for Test {
-                type Balance = u128;
-                type OnFreeBalanceZero = ();
-                type OnNewAccount = ();
-                type Event = ();
+                    Self::deposit_event(RawEvent::ParameterUpdated(
+                        RiskParameter::ReserveFactor(asset, reserve_factor)
+                    ));
 
-                type TransactionPayment = ();
-                type DustRemoval = ();
-                type TransferPayment = ();
-	}
+                    Ok(())
+                }
 
-	type Lending = Module<Test>;
+                fn set_liquidation_incentive(origin, liquidation_incentive: Perbill) -> Result {
+                    ensure_root(origin)?;
 
-	fn build() -> runtime_io::TestExternalities<Blake2Hasher> {
-		let mut t = system::GenesisConfig::<Test>::default()
-                    .build_storage()
-                    .unwrap()
-                    .0;
-                t.extend(balances::GenesisConfig::<Test> {
+                    ensure!(parts(liquidation_incentive) <= MAX_LIQUIDATION_INCENTIVE,
+                            LendingError::InvalidLiquidationIncentive.into());
+
+                    <LiquidationIncentive<T>>::put(liquidation_incentive);
+
+                    Self::deposit_event(RawEvent::ParameterUpdated(
+                        RiskParameter::LiquidationIncentive(liquidation_incentive)
+                    ));
+
+                    Ok(())
+                }
+
+                fn set_close_factor(origin, close_factor: Perbill) -> Result {
+                    ensure_root(origin)?;
+
+                    ensure!(parts(close_factor) >= MIN_CLOSE_FACTOR && parts(close_factor) <= MAX_CLOSE_FACTOR,
+                            LendingError::InvalidCloseFactor.into());
+
+                    <CloseFactor<T>>::put(close_factor);
+
+                    Self::deposit_event(RawEvent::ParameterUpdated(
+                        RiskParameter::CloseFactor(close_factor)
+                    ));
+
+                    Ok(())
+                }
+
+                // a cap below the current total only stops new deposits
+                fn set_supply_cap(origin, asset: T::AssetId, cap: Option<T::Balance>) -> Result {
+                    ensure_root(origin)?;
+
+                    ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+
+                    match cap {
+                        Some(cap) => <SupplyCap<T>>::insert(asset, cap),
+                        None => <SupplyCap<T>>::remove(asset),
+                    }
+
+                    Self::deposit_event(RawEvent::ParameterUpdated(
+                        RiskParameter::SupplyCap(asset, cap)
+                    ));
+
+                    Ok(())
+                }
+
+                // a cap below the current total only stops new borrows
+                fn set_borrow_cap(origin, asset: T::AssetId, cap: Option<T::Balance>) -> Result {
+                    ensure_root(origin)?;
+
+                    ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+
+                    match cap {
+                        Some(cap) => <BorrowCap<T>>::insert(asset, cap),
+                        None => <BorrowCap<T>>::remove(asset),
+                    }
+
+                    Self::deposit_event(RawEvent::ParameterUpdated(
+                        RiskParameter::BorrowCap(asset, cap)
+                    ));
+
+                    Ok(())
+                }
+
+                // applies to fixed-term borrows taken from now on
+                fn set_grace_period(origin, grace_period: u64) -> Result {
+                    ensure_root(origin)?;
+
+                    ensure!(grace_period <= MAX_GRACE_PERIOD, LendingError::InvalidGracePeriod.into());
+
+                    <GracePeriod<T>>::put(grace_period);
+
+                    Self::deposit_event(RawEvent::ParameterUpdated(
+                        RiskParameter::GracePeriod(grace_period)
+                    ));
+
+                    Ok(())
+                }
+
+                // applies to penalty interest not yet charged
+                fn set_penalty_rate(origin, penalty_rate: Perbill) -> Result {
+                    ensure_root(origin)?;
+
+                    ensure!(parts(penalty_rate) <= MAX_PENALTY_RATE,
+                            LendingError::InvalidPenaltyRate.into());
+
+                    <PenaltyRate<T>>::put(penalty_rate);
+
+                    Self::deposit_event(RawEvent::ParameterUpdated(
+                        RiskParameter::PenaltyRate(penalty_rate)
+                    ));
+
+                    Ok(())
+                }
+
+                fn set_pause_guardian(origin, guardian: T::AccountId) -> Result {
+                    ensure_root(origin)?;
+
+                    <PauseGuardian<T>>::put(guardian.clone());
+
+                    Self::deposit_event(RawEvent::PauseGuardianSet(guardian));
+
+                    Ok(())
+                }
+
+                fn pause(origin, asset: T::AssetId, action: Action) -> Result {
+                    // root may pause as well as the guardian
+                    let origin: Option<system::RawOrigin<T::AccountId>> = origin.into();
+                    match origin {
+                        Some(system::RawOrigin::Root) => (),
+                        Some(system::RawOrigin::Signed(sender)) => {
+                            ensure!(Self::pause_guardian() == Some(sender), LendingError::NotPauseGuardian.into());
+                        },
+                        _ => return Err(LendingError::NotPauseGuardian.into()),
+                    }
+
+                    ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+                    <Paused<T>>::insert((asset, action), true);
+
+                    Self::deposit_event(RawEvent::Paused(asset, action));
+
+                    Ok(())
+                }
+
+                fn unpause(origin, asset: T::AssetId, action: Action) -> Result {
+                    ensure_root(origin)?;
+
+                    <Paused<T>>::remove((asset, action));
+
+                    Self::deposit_event(RawEvent::Unpaused(asset, action));
+
+                    Ok(())
+                }
+
+                fn withdraw_reserves(origin, asset: T::AssetId, dest: T::AccountId, amount: T::Balance) -> Result {
+                    ensure_root(origin)?;
+
+                    ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+
+                    Self::accrue_interest(asset)?;
+
+                    let decr_total_reserves = Self::total_reserves(asset)
+                        .checked_sub(&amount)
+                        .ok_or(LendingError::ExceedsReserves)?;
+
+                    // reserves lent out to borrowers cannot be paid out
+                    ensure!(Self::to_u128(amount) <= Self::cash(asset), LendingError::InsufficientLiquidity.into());
+
+                    Self::transfer_funds(
+                        asset,
+                        Self::pool_account(),
+                        dest.clone(),
+                        amount,
+                    )?;
+
+                    <TotalReserves<T>>::insert(asset, decr_total_reserves);
+                    Self::update_rates(asset);
+
+                    Self::deposit_event(RawEvent::ReservesWithdrawn(asset, dest, amount));
+
+                    Ok(())
+                }
+
+                fn reallocate_reserves(origin, asset: T::AssetId, amount: T::Balance) -> Result {
+                    ensure_root(origin)?;
+
+                    ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+
+                    Self::accrue_interest(asset)?;
+
+                    let decr_total_reserves = Self::total_reserves(asset)
+                        .checked_sub(&amount)
+                        .ok_or(LendingError::ExceedsReserves)?;
+
+                    let total_supply = Self::total_supply(asset);
+                    ensure!(!total_supply.is_zero(), LendingError::NoSuppliers.into());
+
+                    // reserves are handed to suppliers by raising the supply
+                    // index, and with it the exchange rate of every share
+                    let incr_total_supply = total_supply
+                        .checked_add(&amount)
+                        .ok_or(LendingError::Overflow)?;
+                    let supply_index = mul_div(
+                        Self::supply_index(asset).into_parts(),
+                        Self::to_u128(incr_total_supply),
+                        Self::to_u128(total_supply),
+                        Rounding::Down,
+                    ).ok_or(LendingError::Overflow)?;
+
+                    <TotalReserves<T>>::insert(asset, decr_total_reserves);
+                    <TotalSupply<T>>::insert(asset, incr_total_supply);
+                    <SupplyIndex<T>>::insert(asset, Fixed::from_parts(supply_index));
+                    Self::update_rates(asset);
+
+                    Self::deposit_event(RawEvent::ReservesReallocated(asset, amount));
+
+                    Ok(())
+                }
+
+                // move an account of the baseline runtime that the migration left
+                // behind, overwritten in its `UserArray` slot or reported by
+                // `AccountNotMigrated`, into a position. storage maps cannot be
+                // enumerated, so root names the account. `provider`, the former
+                // liquidity provider, settles the market's cash: it pays in the
+                // deposit it has held, or is paid back the borrow it lent
+                fn recover_baseline_account(origin, who: T::AccountId, provider: T::AccountId) -> Result {
+                    ensure_root(origin)?;
+
+                    let key = legacy_key(b"UserBalance", &who);
+                    let terms = support::storage::get::<BaselineTerms<T::Balance, T::BlockNumber>>(&key)
+                        .ok_or(LendingError::NoBaselineAccount)?;
+                    let native = T::AssetId::zero();
+                    let pool = Self::pool_account();
+
+                    Self::accrue_interest(native)?;
+
+                    let (from, to) = if terms.deposit {
+                        (provider, pool)
+                    } else {
+                        ensure!(Self::to_u128(terms.balance) <= Self::cash(native),
+                                LendingError::InsufficientLiquidity.into());
+                        (pool, provider)
+                    };
+                    Self::transfer_funds(native, from.clone(), to.clone(), terms.balance)?;
+
+                    if let Err(e) = Self::migrate_baseline_account(native, &who, &terms) {
+                        let _ = Self::transfer_funds(native, to, from, terms.balance);
+                        return Err(e);
+                    }
+                    support::storage::kill(&key);
+                    support::storage::kill(&legacy_key(b"UserIndex", &who));
+                    Self::update_rates(native);
+
+                    Self::deposit_event(RawEvent::BaselineAccountRecovered(who));
+
+                    Ok(())
+                }
+	}
+}
+
+impl<T: Trait> Module<T> {
+    // a factor of 100% would allow borrowing the full
+    // collateral value, leaving no margin for interest
+    fn validate_collateral_factor(collateral_factor: Perbill) -> Result {
+        ensure!(parts(collateral_factor) < BILLION,
+                LendingError::InvalidCollateralFactor.into());
+        Ok(())
+    }
+
+    // a factor of 100% would leave suppliers earning nothing
+    fn validate_reserve_factor(reserve_factor: Perbill) -> Result {
+        ensure!(parts(reserve_factor) < BILLION,
+                LendingError::InvalidReserveFactor.into());
+        Ok(())
+    }
+
+    // the jump rate curve must steepen past a kink inside (0%, 100%),
+    // and stay below 100% a year at full utilization
+    fn validate_rate_model(market: &Market<T::AssetId>) -> Result {
+        let kink = parts(market.kink);
+        ensure!(kink > 0 && kink < BILLION, LendingError::InvalidKink.into());
+        ensure!(parts(market.jump_multiplier) >= parts(market.multiplier),
+                LendingError::InvalidJumpMultiplier.into());
+
+        let max_rate = parts(market.base_rate)
+            + kink * parts(market.multiplier) / BILLION
+            + (BILLION - kink) * parts(market.jump_multiplier) / BILLION;
+        ensure!(max_rate < BILLION, LendingError::RateTooHigh.into());
+
+        Ok(())
+    }
+
+    // account holding every market's liquidity and reserves, derived
+    // from MODULE_ID so that no private key controls it
+    pub fn pool_account() -> T::AccountId {
+        Self::module_account(MODULE_ID)
+    }
+
+    // account holding posted collateral, apart from the pool so that
+    // collateral never counts towards a market's liquidity
+    pub fn collateral_account() -> T::AccountId {
+        Self::module_account(COLLATERAL_ID)
+    }
+
+    fn module_account(id: [u8; 8]) -> T::AccountId {
+        let mut seed = b"modl".to_vec();
+        seed.extend_from_slice(&id);
+        let entropy = runtime_io::blake2_256(&seed);
+        T::AccountId::decode(&mut &entropy[..]).unwrap_or_default()
+    }
+
+    // collateral an account has posted in a market, held in the vault
+    pub fn collateral<K: Borrow<(T::AssetId, T::AccountId)>>(position: K) -> T::Balance {
+        Self::vaulted_collateral(position.borrow())
+    }
+
+    fn set_vaulted_collateral(position: &(T::AssetId, T::AccountId), amount: T::Balance) {
+        if amount.is_zero() {
+            <VaultedCollateral<T>>::remove(position);
+        } else {
+            <VaultedCollateral<T>>::insert(position, amount);
+        }
+    }
+
+    // move each market's unlent liquidity and reserves from the former
+    // liquidity provider into the pool account
+    fn migrate_liquidity_provider(provider: T::AccountId) {
+        let pool = Self::pool_account();
+        Self::endow_module_accounts(&provider);
+
+        for asset in Self::market_ids() {
+            let owed = Self::to_balance(Self::cash(asset));
+            let amount = owed.min(<assets::Module<T>>::free_balance(asset, &provider));
+
+            if <assets::Module<T>>::make_transfer(asset, &provider, &pool, amount).is_ok() {
+                Self::deposit_event(RawEvent::LiquidityMigrated(asset, provider.clone(), amount));
+            }
+        }
+    }
+
+    // top the pool and the collateral vault up to the existential deposit
+    // from `from`, as genesis endows them, so that neither is reaped as
+    // funds leave it. what they hold beyond it is owed to the markets
+    fn endow_module_accounts(from: &T::AccountId) {
+        let native = T::AssetId::zero();
+        let existential_deposit = <balances::Module<T>>::existential_deposit();
+
+        for account in &[Self::pool_account(), Self::collateral_account()] {
+            let shortfall = existential_deposit
+                .saturating_sub(<assets::Module<T>>::free_balance(native, account));
+            if !shortfall.is_zero() {
+                // should this fail, collateral cannot move into the vault,
+                // and the borrowers are reported as not migrated
+                let _ = <assets::Module<T>>::make_transfer(native, from, account, shortfall);
+            }
+        }
+    }
+
+    fn ensure_active(asset: T::AssetId, action: Action) -> Result {
+        ensure!(!Self::is_paused((asset, action)), LendingError::ActionPaused.into());
+        Ok(())
+    }
+
+    // run a call made by `who`, depositing a `CallFailed` event with the
+    // lending error it fails with. only the event is left of the error on
+    // chain, and events of a failed call are kept at this Substrate revision
+    fn reporting_failure<F: FnOnce() -> Result>(who: T::AccountId, call: F) -> Result {
+        let result = call();
+        if let Err(message) = result {
+            if let Some(error) = LendingError::from_message(message) {
+                Self::deposit_event(RawEvent::CallFailed(who, error));
+            }
+        }
+        result
+    }
+
+    // upgrade storage one version at a time up to STORAGE_VERSION
+    fn migrate() {
+        let from = Self::storage_version();
+
+        if from < 1 {
+            Self::migrate_from_baseline();
+        }
+
+        <StorageVersion<T>>::put(STORAGE_VERSION);
+        Self::deposit_event(RawEvent::StorageMigrated(from, STORAGE_VERSION));
+    }
+
+    // version 1 replaced the baseline runtime's single native market, with
+    // scalar u64 aggregates and `Terms` keyed by account, with markets of
+    // positions. the native market is listed as on the dev chain, and every
+    // account found in the baseline `UserArray` moves into a position of it
+    fn migrate_from_baseline() {
+        let native = T::AssetId::zero();
+        if !<Markets<T>>::exists(&native) {
+            let market = Self::baseline_market();
+            <ShareMarket<T>>::insert(market.share_asset, native);
+            <Markets<T>>::insert(native, market);
+            <MarketIds<T>>::mutate(|markets| markets.push(native));
+            <AccrualTime<T>>::insert(native, <timestamp::Module<T>>::now());
+            Self::deposit_event(RawEvent::MarketAdded(native));
+        }
+
+        <CloseFactor<T>>::put(Perbill::from_percent(50));
+        <LiquidationIncentive<T>>::put(Perbill::from_percent(8));
+        <GracePeriod<T>>::put(7 * 24 * 60 * 60);
+        <PenaltyRate<T>>::put(Perbill::from_percent(10));
+
+        for item in &[&b"Lending TotalSupply"[..], b"Lending TotalBorrow", b"Lending UtilRatio", b"Lending UserCount"] {
+            support::storage::kill(item);
+        }
+
+        // the baseline array and index share their keys with the registry
+        // of positions, so both are read and cleared before any position
+        // opens. slots were never removed, so they run from zero without
+        // gaps; a slot may name an account since closed, and an account
+        // overwritten in its slot by a later one is not found
+        let mut accounts = Vec::new();
+        let mut slot = 0u64;
+        while let Some(who) = support::storage::get::<T::AccountId>(&legacy_key(b"UserArray", &slot)) {
+            support::storage::kill(&legacy_key(b"UserArray", &slot));
+            support::storage::kill(&legacy_key(b"UserIndex", &who));
+            if let Some(terms) = support::storage::take::<BaselineTerms<T::Balance, T::BlockNumber>>(
+                &legacy_key(b"UserBalance", &who)
+            ) {
+                accounts.push((who, terms));
+            }
+            slot += 1;
+        }
+        // closing an account indexed the default account
+        support::storage::kill(&legacy_key(b"UserIndex", &T::AccountId::default()));
+
+        // the liquidity provider endows the vault before any collateral
+        // moves into it, and the pool before it hands over liquidity
+        if let Some(provider) = Self::liquidity_provider() {
+            Self::endow_module_accounts(&provider);
+        }
+
+        for (who, terms) in accounts {
+            // an account that cannot be moved keeps its funds with the
+            // former liquidity provider, which only hands over what the
+            // migrated market accounts for
+            // such an account keeps its terms, for `recover_baseline_account`
+            if Self::migrate_baseline_account(native, &who, &terms).is_err() {
+                support::storage::put(&legacy_key(b"UserBalance", &who), &terms);
+                Self::deposit_event(RawEvent::AccountNotMigrated(who));
+            }
+        }
+        Self::update_rates(native);
+    }
+
+    // the native market as listed on the dev chain
+    fn baseline_market() -> Market<T::AssetId> {
+        Market {
+            share_asset: T::AssetId::sa(100),
+            collateral_factor: Perbill::from_percent(75),
+            reserve_factor: Perbill::from_percent(10),
+            base_rate: Perbill::from_percent(2),
+            multiplier: Perbill::from_percent(10),
+            jump_multiplier: Perbill::from_percent(100),
+            kink: Perbill::from_percent(80),
+        }
+    }
+
+    // a deposit becomes shares at the market's exchange rate, and a borrow
+    // a position at its index, both at the balance compounded so far
+    fn migrate_baseline_account(
+        asset: T::AssetId,
+        who: &T::AccountId,
+        terms: &BaselineTerms<T::Balance, T::BlockNumber>
+    ) -> Result {
+        if terms.balance.is_zero() {
+            return Ok(());
+        }
+
+        if terms.deposit {
+            let shares = Self::shares_for(terms.balance, Self::supply_index(asset), Rounding::Down)?;
+            let incr_total_supply = Self::total_supply(asset)
+                .checked_add(&terms.balance)
+                .ok_or(LendingError::Overflow)?;
+
+            <assets::Module<T>>::issue(Self::market(asset).share_asset, who, shares)?;
+            <TotalSupply<T>>::insert(asset, incr_total_supply);
+            Self::open_position(asset, who, PositionKind::Supply)?;
+        } else {
+            let incr_total_borrow = Self::total_borrow(asset)
+                .checked_add(&terms.balance)
+                .ok_or(LendingError::Overflow)?;
+
+            // the baseline reserved the amount borrowed from the borrower's own
+            // balance as collateral, recording the whole reserved balance after.
+            // no other baseline module reserves, and no extrinsic of this runtime
+            // has run yet, so whatever of it is still reserved is lending's and
+            // moves into the vault, straight from the reservation so that
+            // none of it is left free should the move fail
+            let collateral = terms.reserved.min(<assets::Module<T>>::reserved_balance(asset, who));
+            <assets::Module<T>>::repatriate_reserved(asset, who, &Self::collateral_account(), collateral)?;
+            Self::set_vaulted_collateral(&(asset, who.clone()), collateral);
+
+            <TotalBorrow<T>>::insert(asset, incr_total_borrow);
+            let id = Self::open_position(asset, who, PositionKind::Borrow)?;
+            <UserBalance<T>>::insert(id, Terms {
+                balance: terms.balance,
+                index: Self::borrow_index(asset),
+                start_block: terms.start_block,
+                reserved: collateral,
+            });
+        }
+
+        Self::enter_market(asset, who);
+
+        Ok(())
+    }
+
+    fn to_u128(balance: T::Balance) -> u128 {
+        <T::BalanceToU128 as Convert<T::Balance, u128>>::convert(balance)
+    }
+
+    fn to_balance(amount: u128) -> T::Balance {
+        <T::BalanceToU128 as Convert<u128, T::Balance>>::convert(amount)
+    }
+
+    // market cash: supplied liquidity and reserves not currently lent out
+    fn cash(asset: T::AssetId) -> u128 {
+        Self::to_u128(Self::total_supply(asset))
+            .saturating_add(Self::to_u128(Self::total_reserves(asset)))
+            .saturating_sub(Self::to_u128(Self::total_borrow(asset)))
+    }
+
+    // recompute utilization and rates of a market from its aggregates
+    fn update_rates(asset: T::AssetId) {
+        let cash = Self::cash(asset);
+        let borrows = Self::to_u128(Self::total_borrow(asset));
+        let reserves = Self::to_u128(Self::total_reserves(asset));
+
+        <UtilRatio<T>>::insert(asset, utilization(cash, borrows, reserves));
+        <SupplyRate<T>>::insert(asset, T::InterestRateModel::supply_rate(asset, cash, borrows, reserves));
+        <BorrowRate<T>>::insert(asset, T::InterestRateModel::borrow_rate(asset, cash, borrows, reserves));
+    }
+
+    // value of an account's collateral weighted by each market's
+    // collateral factor, and of its outstanding borrows, both in
+    // the unit of account
+    pub fn account_liquidity(who: &T::AccountId) -> result::Result<(u128, u128), &'static str> {
+        let mut capacity: u128 = 0;
+        let mut debt: u128 = 0;
+
+        // an account without borrows has nothing to value exactly, so a
+        // market without a price, such as one just listed, adds no
+        // capacity rather than failing
+        let markets = Self::account_markets(who);
+        let borrowing = markets.iter().any(|asset| Self::borrow_position(*asset, who).is_some());
+
+        for asset in markets {
+            let price = match Self::price_of(asset) {
+                Ok(price) => price,
+                Err(_) if !borrowing => continue,
+                Err(e) => return Err(e),
+            };
+
+            let collateral = Self::value_of(Self::collateral((asset, who.clone())), price)?;
+            capacity = capacity.saturating_add(
+                Self::apply_factor(Self::market(asset).collateral_factor, collateral)
+            );
+
+            let borrowed = Self::value_of(Self::borrow_balance(asset, who)?, price)?;
+            debt = debt.saturating_add(borrowed);
+        }
+
+        Ok((capacity, debt))
+    }
+
+    // borrowing capacity over outstanding borrows,
+    // or `None` when nothing is borrowed
+    pub fn health_factor(who: &T::AccountId) -> result::Result<Option<Fixed>, &'static str> {
+        let (capacity, debt) = Self::account_liquidity(who)?;
+        if debt == 0 {
+            return Ok(None);
+        }
+
+        let health_factor = Fixed::from_rational(capacity, debt, Rounding::Down)
+            .ok_or(LendingError::Overflow)?;
+
+        Ok(Some(health_factor))
+    }
+
+    // balances of an account in every market it supplies to,
+    // borrows from or has posted collateral in
+    pub fn account_summary(who: &T::AccountId) -> result::Result<AccountSummary<T::AssetId, T::Balance>, &'static str> {
+        let mut markets = Vec::new();
+
+        for asset in Self::market_ids() {
+            let borrow = Self::borrow_position(asset, who);
+            let summary = AccountMarket {
+                asset,
+                supplied: Self::supply_balance(asset, who)?,
+                shares: Self::shares_of(asset, who),
+                borrowed: Self::borrow_balance(asset, who)?,
+                collateral: Self::collateral((asset, who.clone())),
+                maturity: borrow.and_then(Self::loan_term)
+                    .map(|term| <T::Moment as As<u64>>::as_(term.maturity)),
+                defaulted: borrow.map_or(false, Self::is_defaulted),
+            };
+
+            if summary != (AccountMarket { asset, ..Default::default() }) {
+                markets.push(summary);
+            }
+        }
+
+        Ok(AccountSummary { markets, health_factor: Self::health_factor(who)? })
+    }
+
+    // aggregates, rates and exchange rate of a market, or `None` if it is not listed
+    pub fn market_summary(asset: T::AssetId) -> result::Result<Option<MarketSummary<T::Balance>>, &'static str> {
+        if !<Markets<T>>::exists(&asset) {
+            return Ok(None);
+        }
+
+        let (supply_index, borrow_index) = Self::current_indices(asset)?;
+        let total_supply = Self::scale(Self::total_supply(asset), Self::supply_index(asset), supply_index)?;
+        let total_borrow = Self::scale(Self::total_borrow(asset), Self::borrow_index(asset), borrow_index)?;
+
+        // reserves not yet accrued are left out, as they
+        // are only retained once interest is accrued
+        Ok(Some(MarketSummary {
+            total_supply,
+            total_borrow,
+            total_reserves: Self::total_reserves(asset),
+            utilization: Self::util_ratio(asset),
+            supply_rate: Self::supply_rate(asset),
+            borrow_rate: Self::borrow_rate(asset),
+            exchange_rate: supply_index,
+        }))
+    }
+
+    // value an account may still borrow against its collateral,
+    // in the unit of account
+    pub fn borrowing_capacity(who: &T::AccountId) -> result::Result<u128, &'static str> {
+        let (capacity, debt) = Self::account_liquidity(who)?;
+        Ok(capacity.saturating_sub(debt))
+    }
+
+    // shares of a market held by an account
+    pub fn shares_of(asset: T::AssetId, who: &T::AccountId) -> T::Balance {
+        <assets::Module<T>>::free_balance(Self::market(asset).share_asset, who)
+    }
+
+    // underlying paid out per share; shares are minted at the
+    // supply index, so the rate grows as interest accrues
+    pub fn exchange_rate(asset: T::AssetId) -> result::Result<Fixed, &'static str> {
+        Ok(Self::current_indices(asset)?.0)
+    }
+
+    // underlying an account's shares redeem for, including accrued interest
+    pub fn supply_balance(asset: T::AssetId, who: &T::AccountId) -> result::Result<T::Balance, &'static str> {
+        Self::underlying_for(Self::shares_of(asset, who), Self::exchange_rate(asset)?)
+    }
+
+    // shares for an amount of underlying, rounded down when minting
+    // and up when burning, so that shares never exceed their backing
+    fn shares_for(amount: T::Balance, exchange_rate: Fixed, rounding: Rounding) -> result::Result<T::Balance, &'static str> {
+        let shares = exchange_rate
+            .checked_div_int(Self::to_u128(amount), rounding)
+            .ok_or(LendingError::Overflow)?;
+
+        Ok(Self::to_balance(shares))
+    }
+
+    // underlying paid out for an amount of shares, rounding down
+    fn underlying_for(shares: T::Balance, exchange_rate: Fixed) -> result::Result<T::Balance, &'static str> {
+        let amount = exchange_rate
+            .checked_mul_int(Self::to_u128(shares), Rounding::Down)
+            .ok_or(LendingError::Overflow)?;
+
+        Ok(Self::to_balance(amount))
+    }
+
+    // outstanding borrow of an account, including accrued interest
+    pub fn borrow_balance(asset: T::AssetId, who: &T::AccountId) -> result::Result<T::Balance, &'static str> {
+        match Self::borrow_position(asset, who) {
+            Some(id) => Self::balance_of(id, asset),
+            None => Ok(Zero::zero()),
+        }
+    }
+
+    // current balance of a borrow, including any penalty
+    // interest not yet charged to it
+    fn balance_of(id: PositionId, asset: T::AssetId) -> result::Result<T::Balance, &'static str> {
+        let balance = Self::accrued_balance(id, asset)?;
+
+        balance
+            .checked_add(&Self::pending_penalty(id, balance)?)
+            .ok_or(LendingError::Overflow.into())
+    }
+
+    // balance of a borrow derived from its principal and the
+    // movement of the borrow index since it was opened
+    fn accrued_balance(id: PositionId, asset: T::AssetId) -> result::Result<T::Balance, &'static str> {
+        let user_data = Self::user_balance(id);
+        let (_, index) = Self::current_indices(asset)?;
+
+        let balance = mul_div(
+            Self::to_u128(user_data.balance),
+            index.into_parts(),
+            user_data.index.into_parts().max(1),
+            Rounding::Down,
+        ).ok_or(LendingError::Overflow)?;
+
+        Ok(Self::to_balance(balance))
+    }
+
+    // whether a fixed-term borrow is unpaid past its grace period. default
+    // follows from the time alone, so nothing is swept or stored per block
+    pub fn is_defaulted(id: PositionId) -> bool {
+        Self::loan_term(id).map_or(false, |term| <timestamp::Module<T>>::now() > term.default_time)
+    }
+
+    // penalty interest owed on the accrued balance of a borrow since it
+    // was last charged; only owed by fixed-term borrows past their default time
+    fn pending_penalty(id: PositionId, balance: T::Balance) -> result::Result<T::Balance, &'static str> {
+        let term = match Self::loan_term(id) {
+            Some(term) => term,
+            None => return Ok(Zero::zero()),
+        };
+
+        let elapsed = <T::Moment as As<u64>>::as_(<timestamp::Module<T>>::now())
+            .saturating_sub(<T::Moment as As<u64>>::as_(term.penalty_time));
+        let growth = Self::accumulate(Fixed::one(), Fixed::from_perbill(Self::penalty_rate()), elapsed)?
+            .saturating_sub(Fixed::one());
+        let penalty = growth
+            .checked_mul_int(Self::to_u128(balance), Rounding::Down)
+            .ok_or(LendingError::Overflow)?;
+
+        Ok(Self::to_balance(penalty))
+    }
+
+    // add penalty interest owed by a defaulted borrow to its balance and to
+    // the market's borrows, retained in full as reserves; expects interest
+    // to be accrued
+    fn charge_penalty(asset: T::AssetId, id: PositionId) -> Result {
+        let mut term = match Self::loan_term(id) {
+            Some(term) => term,
+            None => return Ok(()),
+        };
+        let now = <timestamp::Module<T>>::now();
+        if now <= term.penalty_time {
+            return Ok(());
+        }
+
+        // penalty is charged from the default time on, so a borrow charged
+        // from it has not been touched since defaulting, which is announced
+        let first_charge = term.penalty_time == term.default_time;
+
+        let balance = Self::accrued_balance(id, asset)?;
+        let penalty = Self::pending_penalty(id, balance)?;
+
+        let mut user_data = Self::user_balance(id);
+        user_data.balance = balance.checked_add(&penalty).ok_or(LendingError::Overflow)?;
+        user_data.index = Self::borrow_index(asset);
+
+        let total_borrow = Self::total_borrow(asset)
+            .checked_add(&penalty)
+            .ok_or(LendingError::Overflow)?;
+        let total_reserves = Self::total_reserves(asset)
+            .checked_add(&penalty)
+            .ok_or(LendingError::Overflow)?;
+
+        <UserBalance<T>>::insert(id, user_data);
+        <TotalBorrow<T>>::insert(asset, total_borrow);
+        <TotalReserves<T>>::insert(asset, total_reserves);
+        term.penalty_time = now;
+        <LoanTerms<T>>::insert(id, term);
+        Self::update_rates(asset);
+
+        if first_charge {
+            if let Some(position) = Self::position(id) {
+                Self::deposit_event(RawEvent::LoanDefaulted(id, position.owner, position.asset));
+            }
+        }
+        if !penalty.is_zero() {
+            Self::deposit_event(RawEvent::ReservesAccrued(asset, penalty));
+        }
+
+        Ok(())
+    }
+
+    fn price_of(asset: T::AssetId) -> result::Result<u128, &'static str> {
+        T::PriceFeed::price(asset).ok_or(LendingError::NoPrice.into())
+    }
+
+    // amount of an asset in the unit of account
+    fn value_of(amount: T::Balance, price: u128) -> result::Result<u128, &'static str> {
+        mul_div(Self::to_u128(amount), price, PRICE_ONE, Rounding::Down)
+            .ok_or(LendingError::Overflow.into())
+    }
+
+    // value in the unit of account as an amount of an asset
+    fn amount_of(value: u128, price: u128) -> result::Result<T::Balance, &'static str> {
+        let amount = mul_div(value, PRICE_ONE, price.max(1), Rounding::Down)
+            .ok_or(LendingError::Overflow)?;
+
+        Ok(Self::to_balance(amount))
+    }
+
+    // a fraction of a value, rounding down
+    fn apply_factor(factor: Perbill, value: u128) -> u128 {
+        Fixed::from_perbill(factor)
+            .checked_mul_int(value, Rounding::Down)
+            .unwrap_or(value)
+    }
+
+    fn apply_factor_to(factor: Perbill, amount: T::Balance) -> T::Balance {
+        Self::to_balance(Self::apply_factor(factor, Self::to_u128(amount)))
+    }
+
+    fn enter_market(asset: T::AssetId, who: &T::AccountId) {
+        <AccountMarkets<T>>::mutate(who, |markets| {
+            if !markets.contains(&asset) {
+                markets.push(asset);
+            }
+        });
+    }
+
+    // forget a market once the account has neither collateral nor a position in it
+    // close the account's supply position once it holds no shares of the market
+    fn close_supply_if_empty(asset: T::AssetId, who: &T::AccountId) -> Result {
+        if let Some(id) = Self::supply_position(asset, who) {
+            if Self::shares_of(asset, who).is_zero() {
+                Self::close_position(id)?;
+                Self::exit_market_if_empty(asset, who);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn exit_market_if_empty(asset: T::AssetId, who: &T::AccountId) {
+        if Self::supply_position(asset, who).is_some()
+            || Self::borrow_position(asset, who).is_some()
+            || !Self::collateral((asset, who.clone())).is_zero() {
+            return;
+        }
+
+        <AccountMarkets<T>>::mutate(who, |markets| markets.retain(|entered| *entered != asset));
+        if Self::account_markets(who).is_empty() {
+            <AccountMarkets<T>>::remove(who);
+        }
+    }
+
+    // supply & borrow indices of a market as of the current block,
+    // without writing them to storage
+    pub fn current_indices(asset: T::AssetId) -> result::Result<(Fixed, Fixed), &'static str> {
+        let elapsed = Self::accrual_time(asset).map_or(0, |then| {
+            <T::Moment as As<u64>>::as_(<timestamp::Module<T>>::now())
+                .saturating_sub(<T::Moment as As<u64>>::as_(then))
+        });
+
+        let supply_index = Self::accumulate(Self::supply_index(asset), Self::supply_rate(asset), elapsed)?;
+        let borrow_index = Self::accumulate(Self::borrow_index(asset), Self::borrow_rate(asset), elapsed)?;
+
+        Ok((supply_index, borrow_index))
+    }
+
+    // Index[a,t] = Index[a,t-1] * (1 + r * Δt / year)
+    fn accumulate(index: Fixed, rate: Fixed, seconds: u64) -> result::Result<Fixed, &'static str> {
+        if seconds == 0 {
+            return Ok(index);
+        }
+
+        let growth = mul_div(rate.into_parts(), seconds as u128, SECONDS_PER_YEAR as u128, Rounding::Down)
+            .map(Fixed::from_parts)
+            .ok_or(LendingError::Overflow)?;
+
+        let new_index = index
+            .checked_mul(growth, Rounding::Down)
+            .and_then(|interest| index.checked_add(interest))
+            .ok_or(LendingError::Overflow)?;
+
+        Ok(new_index)
+    }
+
+    // called at the start of every extrinsic touching a market, replacing
+    // the per-account compounding previously done in on_finalize
+    fn accrue_interest(asset: T::AssetId) -> Result {
+        let now = <timestamp::Module<T>>::now();
+        if Self::accrual_time(asset) == Some(now.clone()) {
+            return Ok(());
+        }
+
+        let (supply_index, borrow_index) = Self::current_indices(asset)?;
+
+        // aggregates grow with their index so that utilization
+        // reflects interest accrued since the last extrinsic
+        let total_supply = Self::scale(Self::total_supply(asset), Self::supply_index(asset), supply_index)?;
+        let total_borrow = Self::scale(Self::total_borrow(asset), Self::borrow_index(asset), borrow_index)?;
+
+        // the reserve factor's share of borrow interest is retained,
+        // the remainder having accrued to suppliers through their index
+        let interest = total_borrow.saturating_sub(Self::total_borrow(asset));
+        let new_reserves = Self::apply_factor_to(Self::market(asset).reserve_factor, interest);
+        let total_reserves = Self::total_reserves(asset)
+            .checked_add(&new_reserves)
+            .ok_or(LendingError::Overflow)?;
+
+        <TotalSupply<T>>::insert(asset, total_supply);
+        <TotalBorrow<T>>::insert(asset, total_borrow);
+        <TotalReserves<T>>::insert(asset, total_reserves);
+        <SupplyIndex<T>>::insert(asset, supply_index);
+        <BorrowIndex<T>>::insert(asset, borrow_index);
+        <AccrualTime<T>>::insert(asset, now);
+        Self::update_rates(asset);
+
+        if !new_reserves.is_zero() {
+            Self::deposit_event(RawEvent::ReservesAccrued(asset, new_reserves));
+        }
+
+        Ok(())
+    }
+
+    fn scale(amount: T::Balance, old_index: Fixed, new_index: Fixed) -> result::Result<T::Balance, &'static str> {
+        let scaled = mul_div(
+            Self::to_u128(amount),
+            new_index.into_parts(),
+            old_index.into_parts().max(1),
+            Rounding::Down,
+        ).ok_or(LendingError::Overflow)?;
+
+        Ok(Self::to_balance(scaled))
+    }
+
+    // burn shares for the underlying they redeem, closing the
+    // supply position once no shares remain
+    fn redeem(asset: T::AssetId, who: &T::AccountId, shares: T::Balance, amount: T::Balance) -> Result {
+        // only unlent liquidity can be withdrawn
+        ensure!(Self::to_u128(amount) <= Self::cash(asset), LendingError::InsufficientLiquidity.into());
+
+        // paid out before anything is written, as nothing
+        // written is undone should the transfer fail
+        Self::transfer_funds(
+            asset,
+            Self::pool_account(),
+            who.clone(),
+            amount,
+        )?;
+
+        // callers check the shares are held
+        <assets::Module<T>>::burn(Self::market(asset).share_asset, who, shares)?;
+
+        // rounding in the index may leave the aggregate
+        // marginally below the sum of balances
+        let decr_total_supply = Self::total_supply(asset).saturating_sub(amount);
+        <TotalSupply<T>>::insert(asset, decr_total_supply);
+        Self::update_rates(asset);
+
+        // shares received by transfer are redeemable without a position
+        Self::close_supply_if_empty(asset, who)?;
+
+        Self::deposit_event(RawEvent::SupplyWithdrawn(asset, who.clone(), amount, shares));
+
+        Ok(())
+    }
+
+    // lend from the pool against the account's collateral, adding to
+    // any borrow it already has in the market
+    fn borrow_funds(asset: T::AssetId, who: &T::AccountId, borrow_value: T::Balance) -> Result {
+        let position = (asset, who.clone());
+
+        ensure!(<Markets<T>>::exists(&asset), LendingError::MarketNotFound.into());
+        ensure!(!borrow_value.is_zero(), LendingError::ZeroAmount.into());
+        Self::ensure_active(asset, Action::Borrow)?;
+
+        // new borrows are only priced against fresh prices, for the
+        // borrowed asset and every market backing the account
+        ensure!(T::PriceFeed::is_fresh(asset)
+                && Self::account_markets(who).into_iter().all(T::PriceFeed::is_fresh),
+                LendingError::StalePrice.into());
+
+        // borrow must be covered by posted collateral across markets
+        let borrow_worth = Self::value_of(borrow_value, Self::price_of(asset)?)?;
+        ensure!(borrow_worth <= Self::borrowing_capacity(who)?,
+                LendingError::Undercollateralized.into());
+
+        // bring the borrow index up to date
+        Self::accrue_interest(asset)?;
+
+        let incr_total_borrow = Self::total_borrow(asset)
+            .checked_add(&borrow_value)
+            .ok_or(LendingError::Overflow)?;
+        ensure!(Self::borrow_cap(asset).map_or(true, |cap| incr_total_borrow <= cap),
+                LendingError::BorrowCapExceeded.into());
+        ensure!(Self::to_u128(borrow_value) <= Self::cash(asset),
+                LendingError::InsufficientLiquidity.into());
+
+        // paid out before anything is written, as nothing
+        // written is undone should the transfer fail
+        Self::transfer_funds(
+            asset,
+            Self::pool_account(),
+            who.clone(),
+            borrow_value,
+        )?;
+
+        // Update TotalBorrow to new value
+        <TotalBorrow<T>>::insert(asset, incr_total_borrow);
+        Self::update_rates(asset);
+
+        // an existing borrow is reopened at the current index
+        // with the new amount added to its accrued balance
+        let user_data = if let Some(id) = Self::borrow_position(asset, who) {
+            let outstanding = Self::balance_of(id, asset)?;
+            Terms {
+                balance: outstanding
+                    .checked_add(&borrow_value)
+                    .ok_or(LendingError::Overflow)?,
+                index: Self::borrow_index(asset),
+                reserved: Self::collateral(&position),
+                ..Self::user_balance(id)
+            }
+        } else {
+            Terms {
+                balance: borrow_value,
+                index: Self::borrow_index(asset),
+                start_block: <system::Module<T>>::block_number(),
+                reserved: Self::collateral(&position),
+            }
+        };
+
+        // add struct to storage
+        let id = Self::open_position(asset, who, PositionKind::Borrow)?;
+        <UserBalance<T>>::insert(id, &user_data);
+        Self::enter_market(asset, who);
+
+        Self::deposit_event(RawEvent::CurrencyBorrowed(asset, who.clone(), borrow_value));
+
+        Ok(())
+    }
+
+    // reduce a borrow by an amount paid in by `payer`, reopening what
+    // remains at the current index; expects interest to be accrued
+    fn repay_borrow(asset: T::AssetId, payer: &T::AccountId, borrower: &T::AccountId, amount: T::Balance) -> Result {
+        let id = Self::borrow_position(asset, borrower)
+            .ok_or(LendingError::NoBorrow)?;
+        let remaining = Self::balance_of(id, asset)?
+            .checked_sub(&amount)
+            .ok_or(LendingError::ExceedsBorrow)?;
+
+        // the payment is taken before the borrow is reduced, as
+        // nothing written is undone should the transfer fail
+        Self::transfer_funds(
+            asset,
+            payer.clone(),
+            Self::pool_account(),
+            amount,
+        )?;
+
+        Self::charge_penalty(asset, id)?;
+
+        let decr_total_borrow = Self::total_borrow(asset).saturating_sub(amount);
+        <TotalBorrow<T>>::insert(asset, decr_total_borrow);
+        Self::update_rates(asset);
+
+        if remaining.is_zero() {
+            Self::close_position(id)?;
+            Self::exit_market_if_empty(asset, borrower);
+        } else {
+            let mut user_data = Self::user_balance(id);
+            user_data.balance = remaining;
+            user_data.index = Self::borrow_index(asset);
+            <UserBalance<T>>::insert(id, user_data);
+        }
+
+        Ok(())
+    }
+
+    fn transfer_funds(
+        asset: T::AssetId,
+        outgoing: T::AccountId, 
+        incoming: T::AccountId,
+        transfer_value: T::Balance
+    ) -> Result {
+        // while it generally takes up the same amount of space,
+        // by moving to own function more logic can be added
+        // to the transfer later, if needed 
+        <assets::Module<T>>::make_transfer(
+            asset,
+            &outgoing,
+            &incoming,
+            transfer_value,
+        )?;
+
+        Ok(())
+    }
+
+    // id of the account's supply position in a market, if open
+    pub fn supply_position(asset: T::AssetId, who: &T::AccountId) -> Option<PositionId> {
+        Self::position_id((asset, who.clone(), PositionKind::Supply))
+    }
+
+    // id of the account's borrow position in a market, if open
+    pub fn borrow_position(asset: T::AssetId, who: &T::AccountId) -> Option<PositionId> {
+        Self::position_id((asset, who.clone(), PositionKind::Borrow))
+    }
+
+    // open positions from slot `start` of the registry on, at most `limit`
+    // of them. closing a position moves the last one into its slot, so
+    // paging is only complete while no positions close in between
+    pub fn accounts(start: u64, limit: u64) -> Vec<(PositionId, Position<T::AccountId, T::AssetId>)> {
+        OpenPositions::<T>::page(start, limit)
+            .into_iter()
+            .filter_map(|id| Self::position(id).map(|position| (id, position)))
+            .collect()
+    }
+
+    // every open position owned by an account, oldest first
+    pub fn positions_of(who: &T::AccountId) -> Vec<(PositionId, Position<T::AccountId, T::AssetId>)> {
+        Self::account_positions(who)
+            .into_iter()
+            .filter_map(|id| Self::position(id).map(|position| (id, position)))
+            .collect()
+    }
+
+    // id of the account's position of a kind in a market,
+    // opening a new position if it has none
+    fn open_position(asset: T::AssetId, who: &T::AccountId, kind: PositionKind) -> result::Result<PositionId, &'static str> {
+        let key = (asset, who.clone(), kind);
+        if let Some(id) = Self::position_id(&key) {
+            return Ok(id);
+        }
+
+        let id = Self::next_position_id();
+        let next_id = id.checked_add(1)
+            .ok_or(LendingError::Overflow)?;
+
+        OpenPositions::<T>::insert(&id).ok_or(LendingError::Overflow)?;
+
+        <NextPositionId<T>>::put(next_id);
+        <Positions<T>>::insert(id, Position { owner: who.clone(), asset, kind });
+        <PositionIds<T>>::insert(&key, id);
+        <AccountPositions<T>>::mutate(who, |positions| positions.push(id));
+
+        Self::deposit_event(RawEvent::PositionOpened(id, who.clone(), asset));
+
+        Ok(id)
+    }
+
+    fn close_position(id: PositionId) -> Result {
+        let position = Self::position(id).ok_or(LendingError::NoPosition)?;
+
+        OpenPositions::<T>::remove(&id);
+
+        <Positions<T>>::remove(id);
+        <PositionIds<T>>::remove((position.asset, position.owner.clone(), position.kind));
+        <UserBalance<T>>::remove(id);
+        <LoanTerms<T>>::remove(id);
+        <AccountPositions<T>>::mutate(&position.owner, |positions| positions.retain(|open| *open != id));
+        if Self::account_positions(&position.owner).is_empty() {
+            <AccountPositions<T>>::remove(&position.owner);
+        }
+
+        Self::deposit_event(RawEvent::PositionClosed(id));
+
+        Ok(())
+    }
+}
+
+// a supplier transferring away all of its shares no longer
+// supplies to the market, and its supply position is closed
+impl<T: Trait> assets::OnTransfer<T::AccountId, T::AssetId> for Module<T> {
+    fn on_transfer(asset: T::AssetId, from: &T::AccountId, _to: &T::AccountId) {
+        if <ShareMarket<T>>::exists(&asset) {
+            let _ = Self::close_supply_if_empty(Self::share_market(asset), from);
+        }
+    }
+}
+
+decl_event!(
+	pub enum Event<T> 
+        where 
+            <T as system::Trait>::AccountId,
+            <T as balances::Trait>::Balance,
+            <T as assets::Trait>::AssetId,
+            <T as timestamp::Trait>::Moment,
+        {
+                // market, supplier, amount supplied, shares minted
+                CurrencySupplied(AssetId, AccountId, Balance, Balance),
+                CurrencyBorrowed(AssetId, AccountId, Balance),
+                // market, supplier, amount withdrawn, shares redeemed
+                SupplyWithdrawn(AssetId, AccountId, Balance, Balance),
+                BorrowRepaid(AssetId, AccountId, Balance),
+                CollateralDeposited(AssetId, AccountId, Balance),
+                CollateralWithdrawn(AssetId, AccountId, Balance),
+                ParameterUpdated(RiskParameter<AssetId, Balance>),
+                // market, former liquidity provider, amount moved to the pool account
+                LiquidityMigrated(AssetId, AccountId, Balance),
+                MarketAdded(AssetId),
+                // liquidator, borrower, borrowed asset, amount repaid,
+                // collateral asset, collateral seized
+                Liquidated(AccountId, AccountId, AssetId, Balance, AssetId, Balance),
+                // position, owner, market
+                PositionOpened(PositionId, AccountId, AssetId),
+                PositionClosed(PositionId),
+                // market, amount retained from borrow interest
+                ReservesAccrued(AssetId, Balance),
+                // market, recipient, amount paid out of reserves
+                ReservesWithdrawn(AssetId, AccountId, Balance),
+                // market, amount of reserves handed to suppliers
+                ReservesReallocated(AssetId, Balance),
+                // storage version migrated from, and to
+                StorageMigrated(u32, u32),
+                // account of the baseline runtime that could not be moved into a position
+                AccountNotMigrated(AccountId),
+                // account of the baseline runtime moved into a position by root
+                BaselineAccountRecovered(AccountId),
+                PauseGuardianSet(AccountId),
+                // market, action paused or resumed
+                Paused(AssetId, Action),
+                Unpaused(AssetId, Action),
+                // position, borrower, market, maturity of a fixed-term borrow
+                TermLoanOpened(PositionId, AccountId, AssetId, Moment),
+                // position, borrower, market
+                LoanDefaulted(PositionId, AccountId, AssetId),
+                // caller, and the error a signed lending call failed with
+                CallFailed(AccountId, LendingError),
+	}
+);
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use runtime_io::with_externalities;
+	use primitives::{H256, Blake2Hasher};
+	use support::{ 
+            impl_outer_origin, 
+            traits::ReservableCurrency,
+            assert_ok
+        };
+	use runtime_primitives::{
+		BuildStorage,
+		traits::{BlakeTwo256, IdentityLookup, OnInitialize},
+		testing::{Digest, DigestItem, Header}
+	};
+
+	// events are written to storage as they are deposited
+	fn clear_events() {
+		support::storage::kill(b"System Events");
+		support::storage::kill(b"System EventCount");
+	}
+
+	// as `support::assert_noop`, but for the `CallFailed` event that a
+	// failed lending call deposits
+	macro_rules! assert_noop {
+		( $x:expr , $y:expr ) => {
+			clear_events();
+			let h = runtime_io::storage_root();
+			support::assert_err!($x, $y);
+			clear_events();
+			assert_eq!(h, runtime_io::storage_root());
+		}
+	}
+
+	impl_outer_origin! {
+		pub enum Origin for Test {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+
+	impl system::Trait for Test {
+		type Origin = Origin;
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type Digest = Digest;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type Log = DigestItem;
+	}
+
+	impl super::Trait for Test {
+		type Event = ();
+		type InterestRateModel = JumpRate<Test>;
+		type PriceFeed = TestPrices;
+		type BalanceToU128 = U128Balance;
+        }
+
+        impl assets::Trait for Test {
+                type Event = ();
+                type AssetId = u32;
+                type OnTransfer = Lending;
+        }
+
+        impl timestamp::Trait for Test {
+                type Moment = u64;
+                type OnTimestampSet = ();
+        }
+
+        // the native asset at parity, asset 1 worth twice as much
+        // and going stale after block 100
+        pub struct TestPrices;
+
+        impl PriceFeed<u32> for TestPrices {
+                fn price(asset: u32) -> Option<u128> {
+                    match asset {
+                        0 => Some(PRICE_ONE),
+                        1 => Some(2 * PRICE_ONE),
+                        _ => None,
+                    }
+                }
+
+                fn is_fresh(asset: u32) -> bool {
+                    match asset {
+                        0 => true,
+                        1 => <system::Module<Test>>::block_number() <= 100,
+                        _ => false,
+                    }
+                }
+        }
+
+        impl balances::Trait for Test {
+                type Balance = u128;
+                type OnFreeBalanceZero = ();
+                type OnNewAccount = ();
+                type Event = ();
+
+                type TransactionPayment = ();
+                type DustRemoval = ();
+                type TransferPayment = ();
+	}
+
+	type Lending = Module<Test>;
+	type Balances = balances::Module<Test>;
+	type Assets = assets::Module<Test>;
+
+	fn test_market(share_asset: u32) -> Market<u32> {
+		Market {
+			share_asset: share_asset,
+			collateral_factor: Perbill::from_percent(75),
+			reserve_factor: Perbill::from_percent(0),
+			base_rate: Perbill::from_percent(1),
+			multiplier: Perbill::from_percent(10),
+			jump_multiplier: Perbill::from_percent(100),
+			kink: Perbill::from_percent(80),
+		}
+	}
+
+        fn percent(n: u32) -> Fixed {
+                Fixed::from_perbill(Perbill::from_percent(n))
+        }
+
+        fn ratio(n: u128, d: u128) -> Fixed {
+                Fixed::from_rational(n, d, Rounding::Down).unwrap()
+        }
+
+        // test blocks are a year apart, so that balances
+        // grow by whole annual rates from block to block
+        fn set_block(n: u64) {
+                <system::Module<Test>>::set_block_number(n);
+                <timestamp::Module<Test>>::set_timestamp(n * SECONDS_PER_YEAR);
+        }
+
+        // cash for borrowers to draw on, supplied by account 1
+        fn supply_liquidity(asset: u32) {
+                assert_ok!(Lending::deposit(Origin::signed(1), asset, 10_000));
+        }
+
+	fn build() -> runtime_io::TestExternalities<Blake2Hasher> {
+		let mut t = system::GenesisConfig::<Test>::default()
+                    .build_storage()
+                    .unwrap()
+                    .0;
+                t.extend(balances::GenesisConfig::<Test> {
+                    transaction_base_fee: 0,
+                    transaction_byte_fee: 0,
+                    existential_deposit: 0,
+                    transfer_fee: 0,
+                    creation_fee: 0,
+                    balances: vec![
+                        (1, 1_000_000), // Alice in 'chain_spec.rs' (figuratively)
+                        (2, 1_000_000), // Bob ''
+                        (3, 1_000_000), // Charlie ''
+                        (4, 1_000_000), // Dave ''
+                        (Lending::pool_account(), 1_000_000)], // seeded pool
+                    vesting: vec![],
+                    }
+                    .build_storage()
+                    .unwrap()
+                    .0,
+                    );
+
+                t.extend(
+                    assets::GenesisConfig::<Test> {
+                        balances: vec![
+                            ((1, 1), 1_000_000),
+                            ((1, 2), 1_000_000),
+                            ((1, 3), 1_000_000),
+                            ((1, 4), 1_000_000),
+                            ((1, Lending::pool_account()), 1_000_000)],
+                    }
+                    .build_storage()
+                    .unwrap()
+                    .0,
+                );
+
+                t.extend(
+                    GenesisConfig::<Test> {
+                        markets: vec![(0, test_market(100)), (1, test_market(101))],
+                        close_factor: Perbill::from_percent(50),
+                        liquidation_incentive: Perbill::from_percent(8),
+                        grace_period: SECONDS_PER_YEAR / 2,
+                        penalty_rate: Perbill::from_percent(20),
+                    }
+                    .build_storage()
+                    .unwrap()
+                    .0,
+                );
+                t.into()
+	}
+
+        #[test]
+        fn if_this_fails_something_is_wrong() {
+            with_externalities(&mut build(), || {
+                assert!(true);
+            })
+        }
+
+	#[test]
+	fn user_can_make_a_deposit() {
+            with_externalities(&mut build(), || { 
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                assert_ok!(Lending::deposit(Origin::signed(3), 0, 100));
+            });
+	}
+
+        #[test]
+        fn user_can_make_a_withdraw() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(2), 0));
+            });
+        }
+
+        #[test]
+        fn user_cant_withraw_without_deposit() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::withdraw_in_full(Origin::signed(2), 0), 
+                             LendingError::NoPosition.message());
+            });
+        }
+
+        #[test]
+        fn pool_account_holds_supplied_funds() {
+            with_externalities(&mut build(), || {
+                let pool = Lending::pool_account();
+                assert!(pool > 4);
+                assert_eq!(Lending::pool_account(), pool);
+
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                assert_eq!(Balances::free_balance(&pool), 1_000_100);
+                assert_eq!(Balances::free_balance(&1), 1_000_000);
+            });
+        }
+
+        #[test]
+        fn user_can_borrow() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 100));
+            });
+        }
+
+        #[test]
+        fn user_count_increments_when_supplying() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                assert_eq!(Lending::user_count(), 1);
+            });
+        }
+
+        #[test]
+        fn user_count_decrements_when_withdrawing() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                assert_eq!(Lending::user_count(), 1);
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(2), 0));
+                assert_eq!(Lending::user_count(), 0);
+            });
+        }
+
+        #[test]
+        fn user_count_increments_when_borrowing() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 100));
+                assert_eq!(Lending::user_count(), 2);
+            });
+        }
+
+        #[test]
+        fn user_count_decrements_when_repaid() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 100));
+                assert_eq!(Lending::user_count(), 2);
+                assert_ok!(Lending::repay_in_full(Origin::signed(2), 0));
+                assert_eq!(Lending::user_count(), 1);
+            });
+        }
+
+        // every open position is listed exactly once, at the slot recorded for it
+        fn assert_registry_consistent() {
+                let count = Lending::user_count();
+                let mut listed = Vec::new();
+                for slot in 0..count {
+                    let id = Lending::user_array(slot).expect("slots below the count are filled");
+                    assert_eq!(Lending::user_index(id), Some(slot));
+                    listed.push(id);
+                }
+                assert_eq!(Lending::user_array(count), None);
+
+                let open: Vec<PositionId> = (0..Lending::next_position_id())
+                    .filter(|id| Lending::position(*id).is_some())
+                    .collect();
+                listed.sort();
+                assert_eq!(listed, open);
+        }
+
+        #[test]
+        fn registry_stays_consistent_as_positions_close() {
+            with_externalities(&mut build(), || {
+                for who in 1..5 {
+                    assert_ok!(Lending::deposit(Origin::signed(who), 0, 100));
+                }
+                assert_registry_consistent();
+
+                // the first position is replaced by the last
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(1), 0));
+                assert_eq!(Lending::user_array(0), Some(3));
+                assert_registry_consistent();
+
+                // a middle one, the last one, then the rest
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(3), 0));
+                assert_registry_consistent();
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(4), 0));
+                assert_registry_consistent();
+
+                assert_ok!(Lending::deposit(Origin::signed(1), 1, 100));
+                assert_registry_consistent();
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(2), 0));
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(1), 1));
+                assert_registry_consistent();
+                assert_eq!(Lending::user_count(), 0);
+            });
+        }
+
+        #[test]
+        fn accounts_are_paged_through_the_registry() {
+            with_externalities(&mut build(), || {
+                for who in 1..5 {
+                    assert_ok!(Lending::deposit(Origin::signed(who), 1, 100));
+                }
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 1, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 1, 100));
+
+                let ids = |page: Vec<(PositionId, Position<u64, u32>)>| -> Vec<PositionId> {
+                    page.into_iter().map(|(id, _)| id).collect()
+                };
+                assert_eq!(ids(Lending::accounts(0, 2)), vec![0, 1]);
+                assert_eq!(ids(Lending::accounts(2, 2)), vec![2, 3]);
+                assert_eq!(ids(Lending::accounts(4, 2)), vec![4]);
+                assert_eq!(ids(Lending::accounts(5, 2)), vec![]);
+                assert_eq!(ids(Lending::accounts(0, 0)), vec![]);
+                assert_eq!(ids(Lending::accounts(3, u64::max_value())), vec![3, 4]);
+
+                assert_eq!(Lending::accounts(4, 1), vec![
+                    (4, Position { owner: 2, asset: 1, kind: PositionKind::Borrow }),
+                ]);
+            });
+        }
+
+        #[test]
+        fn user_can_deposit_and_borrow() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 100));
+
+                assert_eq!(Lending::supply_position(0, &2), Some(0));
+                assert_eq!(Lending::borrow_position(0, &2), Some(1));
+                assert_eq!(Lending::user_count(), 2);
+            })
+        }
+
+        #[test]
+        fn user_can_borrow_and_deposit() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 100));
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+
+                // each side is settled independently of the other
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(2), 0));
+                assert_eq!(Lending::borrow_balance(0, &2), Ok(100));
+                assert_eq!(Lending::supply_position(0, &2), None);
+                assert_eq!(Lending::user_count(), 2);
+            })
+        }
+
+        #[test]
+        fn supply_balance_accrues_from_index() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                // ten years at 3% a year
+                set_block(11);
+                assert_eq!(Lending::supply_balance(0, &2), Ok(1_300));
+
+                // reading does not touch the stored index
+                assert_eq!(Lending::supply_index(0), Fixed::one());
+            });
+        }
+
+        #[test]
+        fn borrow_balance_accrues_from_index() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                // ten years at 6% a year
+                set_block(11);
+                assert_eq!(Lending::borrow_balance(0, &3), Ok(800));
+            });
+        }
+
+        #[test]
+        fn interest_accrues_by_elapsed_time() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                // blocks produced without time passing accrue nothing
+                <system::Module<Test>>::set_block_number(50);
+                assert_eq!(Lending::borrow_balance(0, &3), Ok(500));
+
+                // half a year at 6% a year, however many blocks it took
+                <timestamp::Module<Test>>::set_timestamp(SECONDS_PER_YEAR * 3 / 2);
+                assert_eq!(Lending::borrow_balance(0, &3), Ok(515));
+                assert_eq!(Lending::supply_balance(0, &2), Ok(1_015));
+            });
+        }
+
+        #[test]
+        fn markets_start_accruing_when_first_touched() {
+            with_externalities(&mut build(), || {
+                assert_eq!(Lending::accrual_time(0), None);
+
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_eq!(Lending::accrual_time(0), Some(SECONDS_PER_YEAR));
+                assert_eq!(Lending::supply_index(0), Fixed::one());
+
+                assert_ok!(Lending::add_market(Origin::ROOT, 2, test_market(102)));
+                assert_eq!(Lending::accrual_time(2), Some(SECONDS_PER_YEAR));
+            });
+        }
+
+        #[test]
+        fn index_snapshot_is_taken_at_entry() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                set_block(11);
+                assert_ok!(Lending::deposit(Origin::signed(4), 0, 1_300));
+                assert_eq!(Lending::supply_index(0), ratio(13, 10));
+
+                // a late depositor does not earn interest accrued before entry
+                assert_eq!(Lending::shares_of(0, &4), 1_000);
+                assert_eq!(Lending::supply_balance(0, &4), Ok(1_300));
+                assert_eq!(Lending::supply_balance(0, &2), Ok(1_300));
+            });
+        }
+
+        #[test]
+        fn withdraw_pays_out_accrued_interest() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                set_block(11);
+                assert_ok!(Lending::repay_in_full(Origin::signed(3), 0));
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(2), 0));
+                assert_eq!(Balances::free_balance(&2), 1_000_300);
+            });
+        }
+
+        #[test]
+        fn util_ratio_is_borrows_over_cash_plus_borrows() {
+                assert_eq!(utilization(0, 0, 0), percent(0));
+                assert_eq!(utilization(750, 250, 0), percent(25));
+                assert_eq!(utilization(0, 1_000, 0), percent(100));
+                // reserves exceeding cash cannot push utilization past 100%
+                assert_eq!(utilization(100, 1_000, 500), percent(100));
+        }
+
+        #[test]
+        fn rates_follow_jump_rate_curve() {
+            with_externalities(&mut build(), || {
+                // below the kink: 1% + 50% * 10%
+                assert_eq!(JumpRate::<Test>::borrow_rate(0, 500, 500, 0), percent(6));
+                assert_eq!(JumpRate::<Test>::supply_rate(0, 500, 500, 0), percent(3));
+                // at the kink: 1% + 80% * 10%
+                assert_eq!(JumpRate::<Test>::borrow_rate(0, 200, 800, 0), percent(9));
+                // above the kink the jump multiplier applies: 9% + 10% * 100%
+                assert_eq!(JumpRate::<Test>::borrow_rate(0, 100, 900, 0), percent(19));
+            });
+        }
+
+        #[test]
+        fn reserves_raise_utilization() {
+            with_externalities(&mut build(), || {
+                assert_eq!(utilization(600, 400, 0), percent(40));
+                assert_eq!(utilization(600, 400, 200), percent(50));
+            });
+        }
+
+        #[test]
+        fn flat_rate_ignores_utilization() {
+            assert_eq!(FlatRate::borrow_rate(0u32, 1_000, 0, 0), percent(3));
+            assert_eq!(FlatRate::borrow_rate(0u32, 0, 1_000, 0), percent(3));
+            assert_eq!(FlatRate::supply_rate(0u32, 0, 1_000, 0), percent(1));
+        }
+
+        #[test]
+        fn rates_are_recomputed_on_state_change() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_eq!(Lending::util_ratio(0), percent(0));
+                assert_eq!(Lending::borrow_rate(0), percent(1));
+                assert_eq!(Lending::supply_rate(0), percent(0));
+
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+                assert_eq!(Lending::util_ratio(0), percent(50));
+                assert_eq!(Lending::borrow_rate(0), percent(6));
+
+                assert_ok!(Lending::repay_in_full(Origin::signed(3), 0));
+                assert_eq!(Lending::total_borrow(0), 0);
+                assert_eq!(Lending::util_ratio(0), percent(0));
+            });
+        }
+
+        #[test]
+        fn user_cant_borrow_without_collateral() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::borrow(Origin::signed(2), 0, 100),
+                             LendingError::Undercollateralized.message());
+            });
+        }
+
+        #[test]
+        fn borrowing_capacity_follows_collateral_factor() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_eq!(Balances::free_balance(&Lending::collateral_account()), 1_000);
+                assert_eq!(Lending::borrowing_capacity(&2), Ok(750));
+
+                assert_noop!(Lending::borrow(Origin::signed(2), 0, 0),
+                             LendingError::ZeroAmount.message());
+                assert_noop!(Lending::borrow(Origin::signed(2), 0, 751),
+                             LendingError::Undercollateralized.message());
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 750));
+                assert_eq!(Lending::borrowing_capacity(&2), Ok(0));
+            });
+        }
+
+        #[test]
+        fn collateral_withdrawal_cant_leave_account_under_collateralized() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 600));
+
+                // 800 * 75% = 600 still covers the borrow, 799 would not
+                assert_noop!(Lending::withdraw_collateral(Origin::signed(2), 0, 201),
+                             LendingError::Undercollateralized.message());
+                assert_ok!(Lending::withdraw_collateral(Origin::signed(2), 0, 200));
+                assert_eq!(Lending::collateral((0, 2)), 800);
+
+                // once repaid, all collateral may be withdrawn
+                assert_ok!(Lending::repay_in_full(Origin::signed(2), 0));
+                assert_ok!(Lending::withdraw_collateral(Origin::signed(2), 0, 800));
+                assert_eq!(Balances::free_balance(&Lending::collateral_account()), 0);
+                assert_eq!(Lending::collateral((0, 2)), 0);
+            });
+        }
+
+        #[test]
+        fn user_cant_withdraw_more_collateral_than_posted() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 100));
+                assert_noop!(Lending::withdraw_collateral(Origin::signed(2), 0, 101),
+                             LendingError::ExceedsCollateral.message());
+            });
+        }
+
+        #[test]
+        fn collateral_calls_check_market_and_amount() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::deposit_collateral(Origin::signed(2), 0, 0),
+                             LendingError::ZeroAmount.message());
+                assert_noop!(Lending::deposit_collateral(Origin::signed(2), 2, 100),
+                             LendingError::MarketNotFound.message());
+                assert_eq!(Lending::account_markets(&2), Vec::<u32>::new());
+
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 100));
+                assert_noop!(Lending::withdraw_collateral(Origin::signed(2), 0, 0),
+                             LendingError::ZeroAmount.message());
+                assert_noop!(Lending::withdraw_collateral(Origin::signed(2), 2, 100),
+                             LendingError::MarketNotFound.message());
+            });
+        }
+
+        #[test]
+        fn only_root_can_set_collateral_factor() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::set_collateral_factor(Origin::signed(1), 0, Perbill::from_percent(50)),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Lending::set_collateral_factor(Origin::ROOT, 0, Perbill::from_percent(100)),
+                             LendingError::InvalidCollateralFactor.message());
+                assert_ok!(Lending::set_collateral_factor(Origin::ROOT, 0, Perbill::from_percent(50)));
+                assert_eq!(Lending::market(0).collateral_factor, Perbill::from_percent(50));
+            });
+        }
+
+        #[test]
+        fn healthy_borrow_cant_be_liquidated() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 750));
+                assert_noop!(Lending::liquidate(Origin::signed(3), 2, 0, 0, 100),
+                             LendingError::NotLiquidatable.message());
+            });
+        }
+
+        #[test]
+        fn user_cant_liquidate_without_loan() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_noop!(Lending::liquidate(Origin::signed(3), 2, 0, 0, 100),
+                             LendingError::NoBorrow.message());
+            });
+        }
+
+        #[test]
+        fn liquidation_is_bounded_by_close_factor() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 750));
+                assert_ok!(Lending::set_collateral_factor(Origin::ROOT, 0, Perbill::from_percent(50)));
+
+                // 50% of 750
+                assert_noop!(Lending::liquidate(Origin::signed(3), 2, 0, 0, 376),
+                             LendingError::ExceedsCloseFactor.message());
+                assert_ok!(Lending::liquidate(Origin::signed(3), 2, 0, 0, 375));
+            });
+        }
+
+        #[test]
+        fn liquidator_seizes_collateral_with_incentive() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 750));
+                assert_ok!(Lending::set_collateral_factor(Origin::ROOT, 0, Perbill::from_percent(50)));
+
+                assert_ok!(Lending::liquidate(Origin::signed(3), 2, 0, 0, 300));
+
+                // 300 repaid plus an 8% bonus
+                assert_eq!(Balances::free_balance(&3), 1_000_024);
+                assert_eq!(Lending::collateral((0, 2)), 676);
+                assert_eq!(Balances::free_balance(&Lending::collateral_account()), 676);
+                assert_eq!(Lending::borrow_balance(0, &2), Ok(450));
+                assert_eq!(Lending::total_borrow(0), 450);
+            });
+        }
+
+        #[test]
+        fn collateral_leaves_other_reservations_alone() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                // balances set aside by other modules, as staking or democracy would
+                assert_ok!(Balances::reserve(&2, 300));
+                assert_ok!(Assets::reserve(1, &2, 200));
+
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 1, 500));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 500));
+                assert_eq!(Balances::reserved_balance(&2), 300);
+                assert_eq!(Assets::reserved((1, 2)), 200);
+
+                assert_ok!(Lending::repay_in_full(Origin::signed(2), 0));
+                assert_ok!(Lending::withdraw_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::withdraw_collateral(Origin::signed(2), 1, 500));
+                assert_eq!(Balances::reserved_balance(&2), 300);
+                assert_eq!(Balances::free_balance(&2), 999_700);
+                assert_eq!(Assets::reserved((1, 2)), 200);
+                assert_eq!(Assets::balance((1, 2)), 999_800);
+            });
+        }
+
+        #[test]
+        fn slashing_other_reservations_leaves_collateral_intact() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Balances::reserve(&2, 300));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 750));
+                assert_ok!(Lending::set_collateral_factor(Origin::ROOT, 0, Perbill::from_percent(50)));
+
+                // another module takes everything it had reserved
+                Balances::slash_reserved(&2, 300);
+                assert_eq!(Lending::collateral((0, 2)), 1_000);
+
+                // liquidation seizes from the vault, not from the borrower's reserve
+                assert_ok!(Balances::reserve(&2, 100));
+                assert_ok!(Lending::liquidate(Origin::signed(3), 2, 0, 0, 300));
+                assert_eq!(Lending::collateral((0, 2)), 676);
+                assert_eq!(Balances::reserved_balance(&2), 100);
+                assert_eq!(Balances::free_balance(&Lending::collateral_account()), 676);
+            });
+        }
+
+        #[test]
+        fn user_cant_use_unknown_market() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::deposit(Origin::signed(2), 7, 100), LendingError::MarketNotFound.message());
+                assert_noop!(Lending::borrow(Origin::signed(2), 7, 100), LendingError::MarketNotFound.message());
+                assert_noop!(Lending::deposit_collateral(Origin::signed(2), 7, 100),
+                             LendingError::MarketNotFound.message());
+            });
+        }
+
+        #[test]
+        fn markets_keep_separate_aggregates() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit(Origin::signed(2), 1, 400));
+
+                assert_eq!(Lending::total_supply(0), 1_000);
+                assert_eq!(Lending::total_supply(1), 400);
+                assert_eq!(Balances::free_balance(&2), 999_000);
+                assert_eq!(Assets::balance((1, 2)), 999_600);
+                assert_eq!(Assets::balance((1, Lending::pool_account())), 1_000_400);
+            });
+        }
+
+        #[test]
+        fn user_can_borrow_one_asset_against_another() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                // 1_000 of asset 1 at a price of 2, weighted by 75%
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 1, 1_000));
+                assert_eq!(Assets::balance((1, Lending::collateral_account())), 1_000);
+                assert_eq!(Lending::borrowing_capacity(&2), Ok(1_500));
+
+                assert_noop!(Lending::borrow(Origin::signed(2), 0, 1_501),
+                             LendingError::Undercollateralized.message());
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 1_500));
+                assert_eq!(Balances::free_balance(&2), 1_001_500);
+                assert_eq!(Lending::account_markets(&2), vec![1, 0]);
+            });
+        }
+
+        #[test]
+        fn cross_asset_liquidation_seizes_collateral_at_price() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 1, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 1_500));
+                assert_ok!(Lending::set_collateral_factor(Origin::ROOT, 1, Perbill::from_percent(50)));
+
+                // 500 native repaid is worth 540 with the incentive, or 270 of asset 1
+                assert_ok!(Lending::liquidate(Origin::signed(3), 2, 0, 1, 500));
+                assert_eq!(Lending::collateral((1, 2)), 730);
+                assert_eq!(Assets::balance((1, 3)), 1_000_270);
+                assert_eq!(Lending::borrow_balance(0, &2), Ok(1_000));
+            });
+        }
+
+        #[test]
+        fn account_leaves_market_once_empty() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 1, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 100));
+                assert_ok!(Lending::repay_in_full(Origin::signed(2), 0));
+                assert_eq!(Lending::account_markets(&2), vec![1]);
+
+                assert_ok!(Lending::withdraw_collateral(Origin::signed(2), 1, 1_000));
+                assert_eq!(Lending::account_markets(&2), Vec::<u32>::new());
+            });
+        }
+
+        #[test]
+        fn only_root_can_add_market() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::add_market(Origin::signed(1), 2, test_market(102)),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Lending::add_market(Origin::ROOT, 1, test_market(102)),
+                             LendingError::MarketExists.message());
+
+                assert_ok!(Lending::add_market(Origin::ROOT, 2, test_market(102)));
+                assert_eq!(Lending::market(2), test_market(102));
+                assert_eq!(Lending::share_market(102), 2);
+                assert_eq!(Lending::borrow_rate(2), percent(1));
+            });
+        }
+
+        #[test]
+        fn deposit_mints_shares_at_exchange_rate() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_eq!(Assets::balance((100, 2)), 1_000);
+                assert_eq!(Assets::total_issuance(100), 1_000);
+
+                // shares of one market are not shares of another
+                assert_eq!(Lending::shares_of(1, &2), 0);
+
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(2), 0));
+                assert_eq!(Assets::total_issuance(100), 0);
+                assert_eq!(Balances::free_balance(&2), 1_000_000);
+            });
+        }
+
+        #[test]
+        fn exchange_rate_grows_with_interest() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+                assert_eq!(Lending::exchange_rate(0), Ok(Fixed::one()));
+
+                set_block(11);
+                assert_eq!(Lending::exchange_rate(0), Ok(ratio(13, 10)));
+                assert_eq!(Lending::shares_of(0, &2), 1_000);
+            });
+        }
+
+        #[test]
+        fn transferred_shares_redeem_for_underlying() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                assert_ok!(Assets::transfer(Origin::signed(2), 100, 4, 400));
+                assert_eq!(Lending::shares_of(0, &4), 400);
+
+                set_block(11);
+                assert_ok!(Lending::repay_in_full(Origin::signed(3), 0));
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(4), 0));
+                assert_eq!(Balances::free_balance(&4), 1_000_520);
+                assert_eq!(Lending::supply_balance(0, &2), Ok(780));
+            });
+        }
+
+        #[test]
+        fn transferring_away_all_shares_closes_the_supply_position() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                let id = Lending::supply_position(0, &2).unwrap();
+
+                assert_ok!(Assets::transfer(Origin::signed(2), 100, 4, 400));
+                assert_eq!(Lending::supply_position(0, &2), Some(id));
+
+                assert_ok!(Assets::transfer(Origin::signed(2), 100, 4, 600));
+                assert_eq!(Lending::supply_position(0, &2), None);
+                assert!(Lending::positions_of(&2).is_empty());
+                assert!(Lending::accounts(0, 10).is_empty());
+                assert!(Lending::account_markets(&2).is_empty());
+            });
+        }
+
+        #[test]
+        fn withdrawing_in_full_requires_a_market() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::withdraw_in_full(Origin::signed(2), 7),
+                             LendingError::MarketNotFound.message());
+            });
+        }
+
+        #[test]
+        fn share_asset_cant_be_reused() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::add_market(Origin::ROOT, 2, test_market(101)),
+                             LendingError::ShareAssetInUse.message());
+                assert_noop!(Lending::add_market(Origin::ROOT, 2, test_market(1)),
+                             LendingError::ShareAssetInUse.message());
+                assert_noop!(Lending::add_market(Origin::ROOT, 2, test_market(0)),
+                             LendingError::ShareAssetInUse.message());
+            });
+        }
+
+        #[test]
+        fn top_up_deposit_mints_shares_at_current_rate() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 2_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                set_block(11);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_300));
+                assert_eq!(Lending::shares_of(0, &2), 2_000);
+                assert_eq!(Lending::supply_balance(0, &2), Ok(2_600));
+                assert_eq!(Lending::total_supply(0), 2_600);
+                assert_eq!(Lending::user_count(), 2);
+            });
+        }
+
+        #[test]
+        fn partial_withdraw_burns_shares_at_exchange_rate() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 2_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                set_block(11);
+                assert_ok!(Lending::repay_in_full(Origin::signed(3), 0));
+                assert_ok!(Lending::withdraw(Origin::signed(2), 0, 650));
+                assert_eq!(Lending::shares_of(0, &2), 500);
+                assert_eq!(Lending::supply_balance(0, &2), Ok(650));
+                assert_eq!(Lending::total_supply(0), 650);
+                assert_eq!(Balances::free_balance(&2), 999_650);
+
+                // the supplier keeps their position until fully withdrawn
+                assert_eq!(Lending::supply_position(0, &2), Some(0));
+                assert_noop!(Lending::withdraw(Origin::signed(2), 0, 651),
+                             LendingError::ExceedsSupply.message());
+
+                assert_ok!(Lending::withdraw(Origin::signed(2), 0, 650));
+                assert_eq!(Lending::shares_of(0, &2), 0);
+                assert_eq!(Lending::supply_position(0, &2), None);
+            });
+        }
+
+        #[test]
+        fn partial_withdraw_rounds_shares_up() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 2_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                // at an exchange rate of 1.3, one unit costs a whole share
+                set_block(11);
+                assert_ok!(Lending::withdraw(Origin::signed(2), 0, 1));
+                assert_eq!(Lending::shares_of(0, &2), 999);
+
+                assert_noop!(Lending::withdraw(Origin::signed(2), 0, 0),
+                             LendingError::ZeroAmount.message());
+            });
+        }
+
+        #[test]
+        fn withdrawals_and_borrows_are_bounded_by_market_cash() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 2_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                // the pool holds more than the market, but only 500 is unlent
+                assert_noop!(Lending::withdraw(Origin::signed(2), 0, 501),
+                             LendingError::InsufficientLiquidity.message());
+                assert_noop!(Lending::withdraw_in_full(Origin::signed(2), 0),
+                             LendingError::InsufficientLiquidity.message());
+                assert_noop!(Lending::borrow(Origin::signed(3), 0, 501),
+                             LendingError::InsufficientLiquidity.message());
+
+                assert_ok!(Lending::withdraw(Origin::signed(2), 0, 500));
+                assert_eq!(Lending::cash(0), 0);
+            });
+        }
+
+        #[test]
+        fn partial_repay_reopens_borrow_at_current_index() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 2_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                set_block(11);
+                assert_ok!(Lending::repay(Origin::signed(3), 0, 300));
+                assert_eq!(Lending::borrow_balance(0, &3), Ok(500));
+                assert_eq!(Lending::user_balance(1).index, ratio(16, 10));
+                assert_eq!(Lending::total_borrow(0), 500);
+                assert_eq!(Lending::user_count(), 2);
+
+                assert_noop!(Lending::repay(Origin::signed(3), 0, 501),
+                             LendingError::ExceedsBorrow.message());
+
+                // repaying the remainder closes the position
+                assert_ok!(Lending::repay(Origin::signed(3), 0, 500));
+                assert_eq!(Lending::borrow_position(0, &3), None);
+                assert!(!<UserBalance<Test>>::exists(1));
+                assert_eq!(Lending::total_borrow(0), 0);
+                assert_eq!(Lending::user_count(), 1);
+            });
+        }
+
+        #[test]
+        fn incremental_borrow_adds_to_accrued_balance() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 2_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                set_block(11);
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 100));
+                assert_eq!(Lending::borrow_balance(0, &3), Ok(900));
+                assert_eq!(Lending::user_balance(1).index, ratio(16, 10));
+                assert_eq!(Lending::user_balance(1).start_block, 1);
+                assert_eq!(Lending::total_borrow(0), 900);
+                assert_eq!(Lending::user_count(), 2);
+
+                // further borrows remain bounded by collateral
+                assert_noop!(Lending::borrow(Origin::signed(3), 0, 601),
+                             LendingError::Undercollateralized.message());
+            });
+        }
+
+        #[test]
+        fn positions_are_listed_per_account() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 1, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 100));
+                assert_ok!(Lending::deposit(Origin::signed(3), 1, 100));
+                assert_ok!(Lending::deposit(Origin::signed(2), 1, 100));
+
+                assert_eq!(Lending::account_positions(&2), vec![0, 1, 3]);
+                assert_eq!(Lending::positions_of(&2), vec![
+                    (0, Position { owner: 2, asset: 0, kind: PositionKind::Supply }),
+                    (1, Position { owner: 2, asset: 0, kind: PositionKind::Borrow }),
+                    (3, Position { owner: 2, asset: 1, kind: PositionKind::Supply }),
+                ]);
+                assert_eq!(Lending::positions_of(&3), vec![
+                    (2, Position { owner: 3, asset: 1, kind: PositionKind::Supply }),
+                ]);
+
+                // a top-up keeps the existing position
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                assert_eq!(Lending::account_positions(&2), vec![0, 1, 3]);
+            });
+        }
+
+        #[test]
+        fn closed_positions_are_forgotten() {
+            with_externalities(&mut build(), || {
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 100));
+
+                assert_ok!(Lending::repay_in_full(Origin::signed(2), 0));
+                assert_eq!(Lending::position(1), None);
+                assert_eq!(Lending::account_positions(&2), vec![0]);
+
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(2), 0));
+                assert_eq!(Lending::position(0), None);
+                assert!(Lending::account_positions(&2).is_empty());
+
+                // ids are never reused
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                assert_eq!(Lending::supply_position(0, &2), Some(2));
+            });
+        }
+
+        #[test]
+        fn stale_prices_block_borrows() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 1, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+
+                set_block(101);
+
+                // stale collateral price
+                assert_noop!(Lending::borrow(Origin::signed(2), 0, 100), LendingError::StalePrice.message());
+                // stale borrowed asset price
+                assert_noop!(Lending::borrow(Origin::signed(3), 1, 100), LendingError::StalePrice.message());
+
+                // existing positions can still be settled
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 100));
+                assert_ok!(Lending::repay_in_full(Origin::signed(3), 0));
+                assert_ok!(Lending::withdraw_collateral(Origin::signed(2), 1, 1_000));
+            });
+        }
+
+        #[test]
+        fn stale_prices_block_collateral_withdrawals_against_borrows() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 1, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 100));
+
+                set_block(101);
+                assert_noop!(Lending::withdraw_collateral(Origin::signed(2), 1, 10),
+                             LendingError::StalePrice.message());
+
+                // once repaid, collateral is released whatever the price
+                assert_ok!(Lending::repay_in_full(Origin::signed(2), 0));
+                assert_ok!(Lending::withdraw_collateral(Origin::signed(2), 1, 1_000));
+            });
+        }
+
+        #[test]
+        fn unpriced_markets_only_matter_to_borrowers() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                // asset 2 is listed without a price
+                assert_ok!(Lending::add_market(Origin::ROOT, 2, test_market(102)));
+                assert_ok!(Assets::issue(2, &2, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 2, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+
+                assert_eq!(Lending::account_liquidity(&2), Ok((750, 0)));
+                assert_eq!(Lending::account_summary(&2).map(|summary| summary.health_factor), Ok(None));
+                assert_ok!(Lending::withdraw_collateral(Origin::signed(2), 0, 500));
+
+                // a borrower's collateral must all be priced
+                assert_noop!(Lending::borrow(Origin::signed(2), 0, 100), LendingError::StalePrice.message());
+                assert_ok!(Lending::withdraw_collateral(Origin::signed(2), 2, 1_000));
+            });
+        }
+
+        // market 0 retaining a tenth of borrow interest, with a borrow
+        // at 50% utilization left to accrue for ten years
+        fn accrue_with_reserves() {
+            <Markets<Test>>::mutate(0, |market| market.reserve_factor = Perbill::from_percent(10));
+
+            set_block(1);
+            assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+            assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 2_000));
+            assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+            // borrowers pay 6%, suppliers earn 6% * 50% * 90%
+            assert_eq!(Lending::supply_rate(0), Fixed::from_perbill(Perbill::from_billionths(27_000_000)));
+
+            set_block(11);
+            assert_ok!(Lending::accrue_interest(0));
+        }
+
+        #[test]
+        fn reserves_accrue_from_borrow_interest() {
+            with_externalities(&mut build(), || {
+                accrue_with_reserves();
+
+                // 300 of borrow interest, 30 of which is retained
+                assert_eq!(Lending::total_borrow(0), 800);
+                assert_eq!(Lending::total_reserves(0), 30);
+                assert_eq!(Lending::total_supply(0), 1_270);
+                assert_eq!(Lending::supply_balance(0, &2), Ok(1_270));
+            });
+        }
+
+        #[test]
+        fn root_can_withdraw_reserves() {
+            with_externalities(&mut build(), || {
+                accrue_with_reserves();
+
+                assert_noop!(Lending::withdraw_reserves(Origin::signed(2), 0, 4, 20),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Lending::withdraw_reserves(Origin::ROOT, 0, 4, 31),
+                             LendingError::ExceedsReserves.message());
+
+                assert_ok!(Lending::withdraw_reserves(Origin::ROOT, 0, 4, 20));
+                assert_eq!(Lending::total_reserves(0), 10);
+                assert_eq!(Balances::free_balance(&4), 1_000_020);
+            });
+        }
+
+        #[test]
+        fn root_can_reallocate_reserves_to_suppliers() {
+            with_externalities(&mut build(), || {
+                accrue_with_reserves();
+
+                assert_noop!(Lending::reallocate_reserves(Origin::ROOT, 0, 31),
+                             LendingError::ExceedsReserves.message());
+
+                assert_ok!(Lending::reallocate_reserves(Origin::ROOT, 0, 30));
+                assert_eq!(Lending::total_reserves(0), 0);
+                assert_eq!(Lending::total_supply(0), 1_300);
+                assert_eq!(Lending::supply_index(0), ratio(13, 10));
+                assert_eq!(Lending::supply_balance(0, &2), Ok(1_300));
+            });
+        }
+
+        #[test]
+        fn only_root_can_set_rate_model() {
+            with_externalities(&mut build(), || {
+                let (one, two) = (Perbill::from_percent(1), Perbill::from_percent(2));
+                assert_noop!(Lending::set_rate_model(Origin::signed(1), 0, one, one, two, Perbill::from_percent(80)),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Lending::set_rate_model(Origin::ROOT, 0, one, one, two, Perbill::from_percent(0)),
+                             LendingError::InvalidKink.message());
+                assert_noop!(Lending::set_rate_model(Origin::ROOT, 0, one, two, one, Perbill::from_percent(80)),
+                             LendingError::InvalidJumpMultiplier.message());
+                // 50% + 80% * 50% + 20% * 100%
+                assert_noop!(Lending::set_rate_model(Origin::ROOT, 0, Perbill::from_percent(50), Perbill::from_percent(50),
+                                                     Perbill::from_percent(100), Perbill::from_percent(80)),
+                             LendingError::RateTooHigh.message());
+
+                assert_ok!(Lending::set_rate_model(Origin::ROOT, 0, two, one, two, Perbill::from_percent(80)));
+                assert_eq!(Lending::market(0).base_rate, two);
+                assert_eq!(Lending::borrow_rate(0), Fixed::from_perbill(two));
+            });
+        }
+
+        #[test]
+        fn rate_model_change_accrues_at_old_rates() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 2_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                set_block(11);
+                let (one, two) = (Perbill::from_percent(1), Perbill::from_percent(2));
+                assert_ok!(Lending::set_rate_model(Origin::ROOT, 0, two, one, two, Perbill::from_percent(80)));
+                assert_eq!(Lending::borrow_balance(0, &3), Ok(800));
+            });
+        }
+
+        #[test]
+        fn only_root_can_set_reserve_factor() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::set_reserve_factor(Origin::signed(1), 0, Perbill::from_percent(10)),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Lending::set_reserve_factor(Origin::ROOT, 0, Perbill::from_percent(100)),
+                             LendingError::InvalidReserveFactor.message());
+                assert_noop!(Lending::set_reserve_factor(Origin::ROOT, 2, Perbill::from_percent(10)),
+                             LendingError::MarketNotFound.message());
+
+                assert_ok!(Lending::set_reserve_factor(Origin::ROOT, 0, Perbill::from_percent(10)));
+                assert_eq!(Lending::market(0).reserve_factor, Perbill::from_percent(10));
+            });
+        }
+
+        #[test]
+        fn only_root_can_set_liquidation_parameters() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::set_liquidation_incentive(Origin::signed(1), Perbill::from_percent(5)),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Lending::set_liquidation_incentive(Origin::ROOT, Perbill::from_percent(51)),
+                             LendingError::InvalidLiquidationIncentive.message());
+                assert_ok!(Lending::set_liquidation_incentive(Origin::ROOT, Perbill::from_percent(5)));
+                assert_eq!(Lending::liquidation_incentive(), Perbill::from_percent(5));
+
+                assert_noop!(Lending::set_close_factor(Origin::signed(1), Perbill::from_percent(25)),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Lending::set_close_factor(Origin::ROOT, Perbill::from_percent(4)),
+                             LendingError::InvalidCloseFactor.message());
+                assert_noop!(Lending::set_close_factor(Origin::ROOT, Perbill::from_percent(91)),
+                             LendingError::InvalidCloseFactor.message());
+                assert_ok!(Lending::set_close_factor(Origin::ROOT, Perbill::from_percent(25)));
+                assert_eq!(Lending::close_factor(), Perbill::from_percent(25));
+            });
+        }
+
+        #[test]
+        fn liquidity_provider_funds_migrate_to_pool() {
+            with_externalities(&mut build(), || {
+                let pool = Lending::pool_account();
+
+                // a chain whose supplied funds and reserves were held by account 1
+                <LiquidityProvider<Test>>::put(1);
+                <TotalSupply<Test>>::insert(0, 1_000);
+                <TotalBorrow<Test>>::insert(0, 400);
+                <TotalReserves<Test>>::insert(0, 50);
+                <TotalSupply<Test>>::insert(1, 300);
+
+                <Lending as OnInitialize<u64>>::on_initialize(1);
+                assert_eq!(Lending::liquidity_provider(), None);
+                assert_eq!(Balances::free_balance(&1), 999_350);
+                assert_eq!(Balances::free_balance(&pool), 1_000_650);
+                assert_eq!(Assets::balance((1, 1)), 999_700);
+                assert_eq!(Assets::balance((1, pool)), 1_000_300);
+
+                // the migration only runs once
+                <Lending as OnInitialize<u64>>::on_initialize(2);
+                assert_eq!(Balances::free_balance(&1), 999_350);
+            });
+        }
+
+        // a chain of the baseline runtime: no markets, and account 1 as the
+        // liquidity provider holding what was supplied and not lent
+        fn build_baseline() -> runtime_io::TestExternalities<Blake2Hasher> {
+                let mut t = system::GenesisConfig::<Test>::default()
+                    .build_storage()
+                    .unwrap()
+                    .0;
+                t.extend(balances::GenesisConfig::<Test> {
                     transaction_base_fee: 0,
                     transaction_byte_fee: 0,
-                    existential_deposit: 0,
+                    // as on the dev chain, which neither module account meets
+                    existential_deposit: 500,
                     transfer_fee: 0,
                     creation_fee: 0,
                     balances: vec![
-                        (1, 1_000_000), // Alice in 'chain_spec.rs' (figuratively)
-                        (2, 1_000_000), // Bob ''
-                        (3, 1_000_000), // Charlie ''
-                        (4, 1_000_000)], // Dave ''
+                        (1, 1_000_700),
+                        (2, 999_000),
+                        (3, 1_000_500),
+                        (4, 1_000_000),
+                        (5, 999_800)],
                     vesting: vec![],
                     }
                     .build_storage()
                     .unwrap()
                     .0,
                     );
-
-                t.extend(
-                    GenesisConfig::<Test> {
-                        liquidity_provider: 1,
-                    }
-                    .build_storage()
-                    .unwrap()
-                    .0,
-                );
                 t.into()
-	}
+        }
+
+        // writes baseline storage under its raw key, in the baseline encoding
+        fn put_baseline<K: Encode, V: Encode>(item: &[u8], key: &K, value: V) {
+                support::storage::put(&legacy_key(item, key), &value);
+        }
+
+        // `Terms { deposit, balance, interest_rate, start_block, reserved }`
+        fn baseline_terms(deposit: bool, balance: u64, start_block: u64, reserved: u64) -> (bool, u64, Perbill, u64, u64) {
+                let rate = if deposit { Perbill::from_percent(1) } else { Perbill::from_percent(3) };
+                (deposit, balance, rate, start_block, reserved)
+        }
+
+        // account 4 supplied 100 and withdrew it, account 5 supplied 200,
+        // account 2 supplied 1_000 and account 3 borrowed 500 against as
+        // much reserved, each compounded since. closing account 4 left it
+        // in slot 0, and account 2 overwrote account 5 in slot 1
+        fn write_baseline_storage() {
+                support::storage::put(b"Lending LiquidityProvider", &1u64);
+                support::storage::put(b"Lending TotalSupply", &1_300u64);
+                support::storage::put(b"Lending TotalBorrow", &500u64);
+                support::storage::put(b"Lending UtilRatio", &Perbill::from_percent(15));
+
+                assert_ok!(Balances::reserve(&3, 500));
+                put_baseline(b"UserBalance", &2u64, baseline_terms(true, 1_100, 3, 0));
+                put_baseline(b"UserBalance", &3u64, baseline_terms(false, 600, 4, 500));
+                put_baseline(b"UserBalance", &5u64, baseline_terms(true, 220, 2, 0));
+
+                put_baseline(b"UserArray", &0u64, 4u64);
+                put_baseline(b"UserArray", &1u64, 2u64);
+                put_baseline(b"UserArray", &2u64, 3u64);
+                put_baseline(b"UserIndex", &0u64, 0u64);
+                put_baseline(b"UserIndex", &5u64, 1u64);
+                put_baseline(b"UserIndex", &2u64, 1u64);
+                put_baseline(b"UserIndex", &3u64, 2u64);
+                support::storage::put(b"Lending UserCount", &3u64);
+        }
 
         #[test]
-        fn if_this_fails_something_is_wrong() {
+        fn baseline_storage_is_migrated_once() {
+            with_externalities(&mut build_baseline(), || {
+                write_baseline_storage();
+                assert_eq!(Lending::storage_version(), 0);
+
+                <Lending as OnInitialize<u64>>::on_initialize(8);
+                assert_eq!(Lending::storage_version(), STORAGE_VERSION);
+
+                // the native market is listed as on the dev chain
+                assert_eq!(Lending::market_ids(), vec![0]);
+                assert_eq!(Lending::market(0).share_asset, 100);
+                assert_eq!(Lending::share_market(100), 0);
+                assert_eq!(Lending::close_factor(), Perbill::from_percent(50));
+                assert_eq!(Lending::grace_period(), 7 * 24 * 60 * 60);
+
+                // balances carry over as compounded, as shares and a borrow
+                assert_eq!(Lending::supply_balance(0, &2), Ok(1_100));
+                assert_eq!(Assets::balance((100, 2)), 1_100);
+                assert_eq!(Lending::borrow_balance(0, &3), Ok(600));
+                assert_eq!(Lending::collateral((0, 3)), 500);
+                assert_eq!(Balances::reserved_balance(&3), 0);
+                assert_eq!(Balances::free_balance(&Lending::collateral_account()), 1_000);
+                assert_eq!(Lending::total_supply(0), 1_100);
+                assert_eq!(Lending::total_borrow(0), 600);
+                assert_eq!(Lending::account_markets(&3), vec![0]);
+                assert!(!Lending::util_ratio(0).is_zero());
+
+                // closed and overwritten accounts are not found
+                assert_eq!(Lending::supply_position(0, &4), None);
+                assert_eq!(Lending::supply_position(0, &5), None);
+                assert_eq!(Lending::user_count(), 2);
+                assert_registry_consistent();
+                assert!(!support::storage::exists(&legacy_key(b"UserBalance", &2u64)));
+                assert!(!support::storage::exists(&legacy_key(b"UserIndex", &0u64)));
+                assert!(!support::storage::exists(b"Lending TotalSupply"));
+                assert!(!support::storage::exists(b"Lending UtilRatio"));
+
+                // the liquidity provider endows the pool and the vault with
+                // the existential deposit, and hands over the unlent 500
+                assert_eq!(Lending::liquidity_provider(), None);
+                assert_eq!(Balances::free_balance(&1), 999_200);
+                assert_eq!(Balances::free_balance(&Lending::pool_account()), 1_000);
+
+                // the migration only runs once
+                <Lending as OnInitialize<u64>>::on_initialize(9);
+                assert_eq!(Lending::total_supply(0), 1_100);
+                assert_eq!(Assets::balance((100, 2)), 1_100);
+            });
+        }
+
+        #[test]
+        fn migrated_positions_settle_in_the_native_market() {
+            with_externalities(&mut build_baseline(), || {
+                write_baseline_storage();
+                <Lending as OnInitialize<u64>>::on_initialize(8);
+
+                assert_ok!(Lending::repay_in_full(Origin::signed(3), 0));
+                assert_ok!(Lending::withdraw_collateral(Origin::signed(3), 0, 500));
+                assert_eq!(Balances::free_balance(&Lending::collateral_account()), 500);
+                assert_eq!(Balances::free_balance(&3), 999_900);
+
+                // emptying the market leaves the pool its existential deposit
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(2), 0));
+                assert_eq!(Balances::free_balance(&2), 1_000_100);
+                assert_eq!(Balances::free_balance(&Lending::pool_account()), 500);
+                assert_eq!(Lending::user_count(), 0);
+            });
+        }
+
+        #[test]
+        fn overwritten_baseline_accounts_are_recovered_by_root() {
+            with_externalities(&mut build_baseline(), || {
+                write_baseline_storage();
+                <Lending as OnInitialize<u64>>::on_initialize(8);
+                assert_eq!(Lending::supply_position(0, &5), None);
+
+                assert_noop!(Lending::recover_baseline_account(Origin::signed(1), 5, 1),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Lending::recover_baseline_account(Origin::ROOT, 4, 1),
+                             LendingError::NoBaselineAccount.message());
+
+                // the former liquidity provider pays in the 220 it held
+                assert_ok!(Lending::recover_baseline_account(Origin::ROOT, 5, 1));
+                assert_eq!(Lending::supply_balance(0, &5), Ok(220));
+                assert_eq!(Lending::total_supply(0), 1_320);
+                assert_eq!(Balances::free_balance(&1), 998_980);
+                assert_eq!(Balances::free_balance(&Lending::pool_account()), 1_220);
+                assert!(!support::storage::exists(&legacy_key(b"UserBalance", &5u64)));
+                assert_registry_consistent();
+
+                assert_noop!(Lending::recover_baseline_account(Origin::ROOT, 5, 1),
+                             LendingError::NoBaselineAccount.message());
+
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(5), 0));
+                assert_eq!(Balances::free_balance(&5), 1_000_020);
+            });
+        }
+
+        #[test]
+        fn collateral_below_the_existential_deposit_is_vaulted() {
+            with_externalities(&mut build_baseline(), || {
+                write_baseline_storage();
+                // account 3 borrowed against 100 only
+                Balances::unreserve(&3, 400);
+                put_baseline(b"UserBalance", &3u64, baseline_terms(false, 600, 4, 100));
+
+                <Lending as OnInitialize<u64>>::on_initialize(8);
+                assert_eq!(Lending::collateral((0, 3)), 100);
+                assert_eq!(Balances::reserved_balance(&3), 0);
+                assert_eq!(Balances::free_balance(&Lending::collateral_account()), 600);
+                assert_eq!(Lending::borrow_balance(0, &3), Ok(600));
+            });
+        }
+
+        #[test]
+        fn new_chains_start_at_the_current_version() {
             with_externalities(&mut build(), || {
-                assert!(true);
-            })
+                assert_eq!(Lending::storage_version(), STORAGE_VERSION);
+
+                <BorrowIndex<Test>>::insert(0, ratio(12, 10));
+                <Lending as OnInitialize<u64>>::on_initialize(1);
+                assert_eq!(Lending::borrow_index(0), ratio(12, 10));
+            });
         }
 
-	#[test]
-	fn user_can_make_a_deposit() {
-            with_externalities(&mut build(), || { 
-                assert_ok!(Lending::deposit(Origin::signed(2), 100));
-                assert_ok!(Lending::deposit(Origin::signed(3), 100));
+        #[test]
+        fn error_codes_are_stable() {
+                assert_eq!(LendingError::MarketNotFound.code(), 0);
+                assert_eq!(LendingError::InsufficientLiquidity.code(), 14);
+                assert_eq!(LendingError::Undercollateralized.code(), 15);
+                assert_eq!(LendingError::Overflow.code(), 30);
+                assert_eq!(LendingError::Undercollateralized.encode(), vec![15]);
+                assert_eq!(LendingError::BorrowCapExceeded.code(), 34);
+                assert_eq!(LendingError::InsufficientBalance.code(), 38);
+                assert_eq!(LendingError::InvalidPenaltyRate.code(), 40);
+                assert_eq!(LendingError::from_code(LendingError::ALL.len() as u8), None);
+
+                // every error is recovered from its code, and from its message
+                for (code, error) in LendingError::ALL.iter().enumerate() {
+                    assert_eq!(error.code() as usize, code);
+                    assert_eq!(LendingError::from_code(error.code()), Some(*error));
+                    assert_eq!(LendingError::from_message(error.message()), Some(*error));
+                }
+        }
+
+        #[test]
+        fn failed_calls_return_the_error_message() {
+            with_externalities(&mut build(), || {
+                let result = Lending::withdraw_in_full(Origin::signed(2), 0);
+                assert_eq!(result.map_err(LendingError::from_message), Err(Some(LendingError::NoPosition)));
+
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 100));
+                let result = Lending::borrow(Origin::signed(2), 0, 1_000);
+                assert_eq!(result.map_err(LendingError::from_message), Err(Some(LendingError::Undercollateralized)));
+
+                // failures of the transfers lending makes are recovered too
+                let result = Lending::deposit(Origin::signed(5), 1, 100);
+                assert_eq!(result.map_err(LendingError::from_message), Err(Some(LendingError::InsufficientBalance)));
+                assert_eq!(LendingError::from_message("balance too low to send value"),
+                           Some(LendingError::InsufficientBalance));
+                assert_eq!(LendingError::from_message("Overflow encountered issuing asset"),
+                           Some(LendingError::Overflow));
+                assert_eq!(LendingError::from_message("bad origin: expected to be a signed origin"), None);
             });
-	}
+        }
 
         #[test]
-        fn user_can_make_a_withdraw() {
+        fn failed_calls_deposit_their_error() {
             with_externalities(&mut build(), || {
-                assert_ok!(Lending::deposit(Origin::signed(2), 100));
-                assert_ok!(Lending::withdraw_in_full(Origin::signed(2)));
+                clear_events();
+                assert!(Lending::withdraw_in_full(Origin::signed(2), 0).is_err());
+                assert!(Lending::deposit(Origin::signed(5), 1, 100).is_err());
+                assert_eq!(<system::Module<Test>>::events().len(), 2);
+
+                // without a signed origin there is no caller to report
+                assert!(Lending::deposit(Origin::ROOT, 0, 100).is_err());
+                assert_eq!(<system::Module<Test>>::events().len(), 2);
             });
         }
 
         #[test]
-        fn user_cant_withraw_without_deposit() {
+        fn guardian_pauses_and_root_resumes() {
             with_externalities(&mut build(), || {
-                assert_noop!(Lending::withdraw_in_full(Origin::signed(2)), 
-                             "User does not have an existing account.");
+                assert_noop!(Lending::set_pause_guardian(Origin::signed(4), 4),
+                             "bad origin: expected to be a root origin");
+                assert_ok!(Lending::set_pause_guardian(Origin::ROOT, 4));
+                assert_eq!(Lending::pause_guardian(), Some(4));
+
+                assert_noop!(Lending::pause(Origin::signed(3), 0, Action::Supply),
+                             LendingError::NotPauseGuardian.message());
+                assert_noop!(Lending::pause(Origin::signed(4), 7, Action::Supply),
+                             LendingError::MarketNotFound.message());
+                assert_ok!(Lending::pause(Origin::signed(4), 0, Action::Supply));
+                assert_ok!(Lending::pause(Origin::ROOT, 0, Action::Borrow));
+                assert!(Lending::is_paused((0, Action::Supply)));
+                assert!(Lending::is_paused((0, Action::Borrow)));
+
+                // only root resumes
+                assert_noop!(Lending::unpause(Origin::signed(4), 0, Action::Supply),
+                             "bad origin: expected to be a root origin");
+                assert_ok!(Lending::unpause(Origin::ROOT, 0, Action::Supply));
+                assert!(!Lending::is_paused((0, Action::Supply)));
+                assert!(Lending::is_paused((0, Action::Borrow)));
             });
         }
 
         #[test]
-        fn check_liquidity_provider() {
+        fn paused_actions_are_rejected_per_market() {
             with_externalities(&mut build(), || {
-                assert_eq!(Lending::liquidity_provider(), 1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+
+                assert_ok!(Lending::pause(Origin::ROOT, 0, Action::Supply));
+                assert_noop!(Lending::deposit(Origin::signed(2), 0, 100), LendingError::ActionPaused.message());
+                assert_ok!(Lending::deposit(Origin::signed(2), 1, 100));
+
+                assert_ok!(Lending::pause(Origin::ROOT, 0, Action::Withdraw));
+                assert_noop!(Lending::withdraw(Origin::signed(2), 0, 100), LendingError::ActionPaused.message());
+                assert_noop!(Lending::withdraw_in_full(Origin::signed(2), 0), LendingError::ActionPaused.message());
+                assert_noop!(Lending::withdraw_collateral(Origin::signed(3), 0, 100),
+                             LendingError::ActionPaused.message());
+                assert_ok!(Lending::withdraw_in_full(Origin::signed(2), 1));
+
+                assert_ok!(Lending::pause(Origin::ROOT, 0, Action::Borrow));
+                assert_noop!(Lending::borrow(Origin::signed(3), 0, 100), LendingError::ActionPaused.message());
             });
         }
 
         #[test]
-        fn user_can_borrow() {
+        fn repayments_are_never_paused() {
+            with_externalities(&mut build(), || {
+                supply_liquidity(0);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 750));
+                assert_ok!(Lending::set_collateral_factor(Origin::ROOT, 0, Perbill::from_percent(50)));
+
+                for action in &[Action::Supply, Action::Borrow, Action::Liquidate, Action::Withdraw] {
+                    assert_ok!(Lending::pause(Origin::ROOT, 0, *action));
+                }
+                assert_noop!(Lending::liquidate(Origin::signed(3), 2, 0, 0, 300),
+                             LendingError::ActionPaused.message());
+
+                assert_ok!(Lending::repay(Origin::signed(2), 0, 300));
+                assert_ok!(Lending::repay_in_full(Origin::signed(2), 0));
+                assert_eq!(Lending::total_borrow(0), 0);
+            });
+        }
+
+        #[test]
+        fn repayment_without_funds_changes_nothing() {
             with_externalities(&mut build(), || {
-                assert_ok!(Lending::borrow(Origin::signed(2), 100));
+                supply_liquidity(1);
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 2_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 1, 500));
+                assert_ok!(Lending::set_collateral_factor(Origin::ROOT, 0, Perbill::from_percent(25)));
+
+                // the borrower spends everything it holds of the asset
+                assert_ok!(Assets::transfer(Origin::signed(2), 1, 4, 1_000_500));
+
+                assert_noop!(Lending::repay(Origin::signed(2), 1, 100),
+                             "Insufficient asset balance for transfer");
+                assert_noop!(Lending::repay_in_full(Origin::signed(2), 1),
+                             "Insufficient asset balance for transfer");
+
+                // nor can a liquidator without funds seize collateral
+                assert_noop!(Lending::liquidate(Origin::signed(5), 2, 1, 0, 100),
+                             "Insufficient asset balance for transfer");
+                assert_eq!(Lending::borrow_balance(1, &2), Ok(500));
+                assert_eq!(Lending::collateral((0, 2)), 2_000);
             });
         }
 
         #[test]
-        fn user_count_increments_when_supplying() {
+        fn root_sets_and_lifts_caps() {
             with_externalities(&mut build(), || {
-                assert_ok!(Lending::deposit(Origin::signed(2), 100));
-                assert_eq!(Lending::user_count(), 1);
+                assert_noop!(Lending::set_supply_cap(Origin::signed(2), 0, Some(1_000)),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Lending::set_borrow_cap(Origin::ROOT, 7, Some(1_000)),
+                             LendingError::MarketNotFound.message());
+
+                assert_eq!(Lending::supply_cap(0), None);
+                assert_ok!(Lending::set_supply_cap(Origin::ROOT, 0, Some(1_000)));
+                assert_ok!(Lending::set_borrow_cap(Origin::ROOT, 0, Some(500)));
+                assert_eq!(Lending::supply_cap(0), Some(1_000));
+                assert_eq!(Lending::borrow_cap(0), Some(500));
+
+                assert_ok!(Lending::set_supply_cap(Origin::ROOT, 0, None));
+                assert_eq!(Lending::supply_cap(0), None);
             });
         }
 
         #[test]
-        fn user_count_decrements_when_withdrawing() {
+        fn deposits_are_bounded_by_supply_cap() {
             with_externalities(&mut build(), || {
-                assert_ok!(Lending::deposit(Origin::signed(2), 100));
-                assert_eq!(Lending::user_count(), 1);
-                assert_ok!(Lending::withdraw_in_full(Origin::signed(2)));
-                assert_eq!(Lending::user_count(), 0);
+                assert_ok!(Lending::set_supply_cap(Origin::ROOT, 0, Some(1_000)));
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 600));
+
+                assert_noop!(Lending::deposit(Origin::signed(3), 0, 401),
+                             LendingError::SupplyCapExceeded.message());
+                assert_ok!(Lending::deposit(Origin::signed(3), 0, 400));
+                assert_eq!(Lending::total_supply(0), 1_000);
+
+                // other markets are unaffected
+                assert_ok!(Lending::deposit(Origin::signed(3), 1, 5_000));
             });
         }
 
         #[test]
-        fn user_count_increments_when_borrowing() {
+        fn borrows_are_bounded_by_borrow_cap() {
             with_externalities(&mut build(), || {
-                assert_ok!(Lending::borrow(Origin::signed(2), 100));
-                assert_eq!(Lending::user_count(), 1);
+                supply_liquidity(0);
+                assert_ok!(Lending::set_borrow_cap(Origin::ROOT, 0, Some(500)));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 300));
+
+                assert_noop!(Lending::borrow(Origin::signed(2), 0, 201),
+                             LendingError::BorrowCapExceeded.message());
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 200));
+
+                // repaying frees room under the cap
+                assert_ok!(Lending::repay(Origin::signed(2), 0, 100));
+                assert_ok!(Lending::borrow(Origin::signed(2), 0, 100));
+                assert_eq!(Lending::total_borrow(0), 500);
             });
         }
 
         #[test]
-        fn user_count_decrements_when_repaid() {
+        fn added_markets_are_listed() {
             with_externalities(&mut build(), || {
-                assert_ok!(Lending::borrow(Origin::signed(2), 100));
-                assert_eq!(Lending::user_count(), 1);
-                assert_ok!(Lending::repay_in_full(Origin::signed(2)));
-                assert_eq!(Lending::user_count(), 0);
+                assert_eq!(Lending::market_ids(), vec![0, 1]);
+                assert_ok!(Lending::add_market(Origin::ROOT, 2, test_market(102)));
+                assert_eq!(Lending::market_ids(), vec![0, 1, 2]);
             });
         }
 
         #[test]
-        fn user_cant_deposit_and_borrow() {
+        fn account_summary_reports_accrued_balances() {
             with_externalities(&mut build(), || {
-                assert_ok!(Lending::deposit(Origin::signed(2), 100));
-                assert_noop!(Lending::borrow(Origin::signed(2), 100), 
-                             "User has an existing loan.");
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 1, 1_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
 
-            })
+                set_block(11);
+                assert_eq!(Lending::account_summary(&2), Ok(AccountSummary {
+                    markets: vec![AccountMarket { asset: 0, supplied: 1_300, shares: 1_000, ..Default::default() }],
+                    health_factor: None,
+                }));
+
+                // 1,000 of asset 1 at twice parity and 75%, against 800 borrowed
+                assert_eq!(Lending::account_summary(&3), Ok(AccountSummary {
+                    markets: vec![
+                        AccountMarket { asset: 0, borrowed: 800, ..Default::default() },
+                        AccountMarket { asset: 1, collateral: 1_000, ..Default::default() },
+                    ],
+                    health_factor: Some(ratio(15, 8)),
+                }));
+
+                assert_eq!(Lending::account_summary(&4), Ok(AccountSummary::default()));
+            });
         }
 
         #[test]
-        fn user_cant_borrow_and_deposit() {
+        fn market_summary_reports_accrued_aggregates() {
             with_externalities(&mut build(), || {
-                assert_ok!(Lending::borrow(Origin::signed(2), 100));
-                assert_noop!(Lending::deposit(Origin::signed(2), 100), 
-                             "User has an existing deposit.");
-            })
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 2_000));
+                assert_ok!(Lending::borrow(Origin::signed(3), 0, 500));
+
+                set_block(11);
+                assert_eq!(Lending::market_summary(0), Ok(Some(MarketSummary {
+                    total_supply: 1_300,
+                    total_borrow: 800,
+                    total_reserves: 0,
+                    utilization: percent(50),
+                    supply_rate: percent(3),
+                    borrow_rate: percent(6),
+                    exchange_rate: ratio(13, 10),
+                })));
+
+                assert_eq!(Lending::market_summary(2), Ok(None));
+            });
+        }
+
+        // account 3 borrows 500 at 6% a year, due at the end of year 2
+        // and in default from half a year later
+        fn open_term_loan() -> PositionId {
+                set_block(1);
+                assert_ok!(Lending::deposit(Origin::signed(2), 0, 1_000));
+                assert_ok!(Lending::deposit_collateral(Origin::signed(3), 0, 1_000));
+                assert_ok!(Lending::borrow_fixed_term(Origin::signed(3), 0, 500, 2 * SECONDS_PER_YEAR));
+
+                Lending::borrow_position(0, &3).unwrap()
+        }
+
+        #[test]
+        fn fixed_term_borrows_default_after_grace_period() {
+            with_externalities(&mut build(), || {
+                set_block(1);
+                assert_noop!(Lending::borrow_fixed_term(Origin::signed(3), 0, 500, SECONDS_PER_YEAR),
+                             LendingError::InvalidMaturity.message());
+
+                assert_noop!(Lending::borrow_fixed_term(Origin::signed(3), 0, 0, 2 * SECONDS_PER_YEAR),
+                             LendingError::ZeroAmount.message());
+
+                let id = open_term_loan();
+                assert_eq!(Lending::loan_term(id), Some(LoanTerm {
+                    maturity: 2 * SECONDS_PER_YEAR,
+                    default_time: 2 * SECONDS_PER_YEAR + SECONDS_PER_YEAR / 2,
+                    penalty_time: 2 * SECONDS_PER_YEAR + SECONDS_PER_YEAR / 2,
+                }));
+                assert_noop!(Lending::borrow_fixed_term(Origin::signed(3), 0, 100, 3 * SECONDS_PER_YEAR),
+                             LendingError::BorrowExists.message());
+
+                // matured, but within its grace period
+                <timestamp::Module<Test>>::set_timestamp(2 * SECONDS_PER_YEAR + SECONDS_PER_YEAR / 4);
+                assert_noop!(Lending::borrow(Origin::signed(3), 0, 100), LendingError::LoanMatured.message());
+                assert_noop!(Lending::liquidate(Origin::signed(4), 3, 0, 0, 100),
+                             LendingError::NotLiquidatable.message());
+                assert!(!Lending::is_defaulted(id));
+
+                // in default from the end of the grace period, as keepers
+                // read from the account summary
+                set_block(3);
+                assert!(Lending::is_defaulted(id));
+                let summary = Lending::account_summary(&3).unwrap();
+                assert_eq!(summary.markets[0].maturity, Some(2 * SECONDS_PER_YEAR));
+                assert!(summary.markets[0].defaulted);
+
+                // two years at 6%, then half a year's penalty at 20% on 560
+                assert_eq!(Lending::borrow_balance(0, &3), Ok(616));
+
+                assert_ok!(Lending::repay(Origin::signed(3), 0, 16));
+                assert!(Lending::is_defaulted(id));
+                assert_eq!(Lending::borrow_balance(0, &3), Ok(600));
+
+                assert_ok!(Lending::repay_in_full(Origin::signed(3), 0));
+                assert_eq!(Lending::loan_term(id), None);
+                assert_eq!(Lending::total_borrow(0), 0);
+                assert_eq!(Lending::total_reserves(0), 56);
+            });
+        }
+
+        #[test]
+        fn defaulted_borrows_are_liquidated_in_full() {
+            with_externalities(&mut build(), || {
+                let id = open_term_loan();
+
+                // healthy, but in default
+                set_block(3);
+                assert!(Lending::is_defaulted(id));
+                assert_ok!(Lending::liquidate(Origin::signed(4), 3, 0, 0, 616));
+
+                // 616 repaid plus an 8% bonus
+                assert_eq!(Lending::collateral((0, 3)), 335);
+                assert_eq!(Lending::borrow_position(0, &3), None);
+                assert_eq!(Lending::loan_term(id), None);
+                assert_eq!(Lending::total_borrow(0), 0);
+                assert_eq!(Lending::total_reserves(0), 56);
+            });
+        }
+
+        #[test]
+        fn only_root_can_set_default_parameters() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Lending::set_grace_period(Origin::signed(1), 0),
+                             "bad origin: expected to be a root origin");
+                assert_noop!(Lending::set_penalty_rate(Origin::signed(1), Perbill::from_percent(5)),
+                             "bad origin: expected to be a root origin");
+
+                assert_noop!(Lending::set_grace_period(Origin::ROOT, SECONDS_PER_YEAR + 1),
+                             LendingError::InvalidGracePeriod.message());
+                assert_noop!(Lending::set_penalty_rate(Origin::ROOT, Perbill::from_percent(51)),
+                             LendingError::InvalidPenaltyRate.message());
+
+                assert_ok!(Lending::set_grace_period(Origin::ROOT, 0));
+                assert_ok!(Lending::set_penalty_rate(Origin::ROOT, Perbill::from_percent(5)));
+                assert_eq!(Lending::grace_period(), 0);
+                assert_eq!(Lending::penalty_rate(), Perbill::from_percent(5));
+
+                // the grace period is fixed when a borrow is taken
+                let id = open_term_loan();
+                assert_eq!(Lending::loan_term(id).unwrap().default_time, 2 * SECONDS_PER_YEAR);
+            });
         }
 }
@@ -18,7 +18,7 @@ use runtime_primitives::{
 };
 use client::{
 	block_builder::api::{CheckInherentsResult, InherentData, self as block_builder_api},
-	runtime_api, impl_runtime_apis
+	runtime_api, impl_runtime_apis, decl_runtime_apis
 };
 use version::RuntimeVersion;
 #[cfg(feature = "std")]
@@ -31,6 +31,8 @@ pub use consensus::Call as ConsensusCall;
 pub use timestamp::Call as TimestampCall;
 pub use balances::Call as BalancesCall;
 pub use runtime_primitives::{Permill, Perbill};
+pub use lending::{Market, AccountMarket, AccountSummary, MarketSummary, LendingError, Position, PositionId, PositionKind};
+pub use fixed::Fixed;
 pub use timestamp::BlockPeriod;
 pub use support::{StorageValue, construct_runtime};
 
@@ -55,9 +57,27 @@ pub type BlockNumber = u64;
 /// Index of an account's extrinsic in the chain.
 pub type Nonce = u64;
 
+/// The type for recording an account's balance.
+pub type Balance = u128;
+
+/// Identifier of an asset lent and borrowed in `./lending.rs`.
+pub type AssetId = u32;
+
 /// Used for the module template in `./template.rs`
 mod lending;
 
+/// Ledger of non-native assets lent and borrowed in `./lending.rs`
+mod assets;
+
+/// Prices fed to the markets in `./lending.rs`
+mod oracle;
+
+/// Fixed-point arithmetic for rates and indices in `./lending.rs`
+mod fixed;
+
+/// Storage-backed set enumerable by index, for the registry of positions in `./lending.rs`
+mod iterable_set;
+
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
 /// of data like extrinsics, allowing for them to continue syncing the network through upgrades
@@ -95,7 +115,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("lending"),
 	impl_name: create_runtime_str!("lending"),
 	authoring_version: 3,
-	spec_version: 4,
+	spec_version: 5,
 	impl_version: 4,
 	apis: RUNTIME_API_VERSIONS,
 };
@@ -168,7 +188,7 @@ impl timestamp::Trait for Runtime {
 
 impl balances::Trait for Runtime {
 	/// The type for recording an account's balance.
-	type Balance = u128;
+	type Balance = Balance;
 	/// What to do if an account's free balance gets zeroed.
 	type OnFreeBalanceZero = ();
 	/// What to do if a new account is created.
@@ -187,9 +207,31 @@ impl sudo::Trait for Runtime {
 	type Proposal = Call;
 }
 
+impl assets::Trait for Runtime {
+	/// The uniquitous event type.
+	type Event = Event;
+	/// Asset `0` is the native currency held in `Balances`.
+	type AssetId = AssetId;
+	/// Lending follows transfers of its share assets.
+	type OnTransfer = Lending;
+}
+
+impl oracle::Trait for Runtime {
+	/// The uniquitous event type.
+	type Event = Event;
+	/// Priced assets are the assets lent in `Lending`.
+	type AssetId = AssetId;
+}
+
 /// Used for the module template in `./template.rs`
 impl lending::Trait for Runtime {
 	type Event = Event;
+	/// Rates follow utilization, with a steeper slope past the kink.
+	type InterestRateModel = lending::JumpRate<Runtime>;
+	/// Median of the prices submitted by the oracle's feeders.
+	type PriceFeed = Oracle;
+	/// Balances are u128 already.
+	type BalanceToU128 = lending::U128Balance;
 }
 
 construct_runtime!(
@@ -205,6 +247,8 @@ construct_runtime!(
 		Indices: indices,
 		Balances: balances,
 		Sudo: sudo,
+		Assets: assets::{Module, Call, Storage, Event<T>, Config<T>},
+		Oracle: oracle::{Module, Call, Storage, Event<T>, Config<T>},
 		// Used for the module template in `./template.rs`
 		Lending: lending::{Module, Call, Storage, Event<T>, Config<T>},
 	}
@@ -227,6 +271,19 @@ pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, Nonce, Call>;
 /// Executive: handles dispatch to the various modules.
 pub type Executive = executive::Executive<Runtime, Block, Context, Balances, AllModules>;
 
+decl_runtime_apis! {
+	/// Queries over the lending markets, with interest accrued to the queried block.
+	pub trait LendingApi {
+		/// Supplied and borrowed amounts of an account in each market, and its health factor.
+		/// `None` if its balances overflow.
+		fn account_summary(who: AccountId) -> Option<AccountSummary<AssetId, Balance>>;
+		/// Aggregates, rates and utilization of a market, `None` if it is not listed.
+		fn market_summary(asset: AssetId) -> Option<MarketSummary<Balance>>;
+		/// Open positions from slot `start` of the registry on, at most `limit` of them.
+		fn accounts(start: u64, limit: u64) -> Vec<(PositionId, Position<AccountId, AssetId>)>;
+	}
+}
+
 // Implement our runtime API endpoints. This is just a bunch of proxying.
 impl_runtime_apis! {
 	impl runtime_api::Core<Block> for Runtime {
@@ -298,4 +355,18 @@ impl_runtime_apis! {
 			Consensus::authorities()
 		}
 	}
+
+	impl LendingApi<Block> for Runtime {
+		fn account_summary(who: AccountId) -> Option<AccountSummary<AssetId, Balance>> {
+			Lending::account_summary(&who).ok()
+		}
+
+		fn market_summary(asset: AssetId) -> Option<MarketSummary<Balance>> {
+			Lending::market_summary(asset).ok().and_then(|summary| summary)
+		}
+
+		fn accounts(start: u64, limit: u64) -> Vec<(PositionId, Position<AccountId, AssetId>)> {
+			Lending::accounts(start, limit)
+		}
+	}
 }
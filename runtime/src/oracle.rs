@@ -0,0 +1,353 @@
+use support::{
+    decl_module,
+    decl_storage,
+    decl_event,
+    StorageValue,
+    StorageMap,
+    dispatch::Result,
+    ensure,
+    Parameter,
+};
+use system::{ ensure_signed, ensure_root };
+use rstd::prelude::*;
+use runtime_primitives::traits::{ As, Member, MaybeSerializeDebug, Zero };
+use crate::lending::{ PriceFeed, PRICE_ONE };
+
+// blocks a price stays fresh for on chains upgraded from before the
+// oracle, an hour as on the dev chain
+const BASELINE_MAX_PRICE_AGE: u64 = 360;
+
+// prices are submitted per asset by a governance-managed set of feeders,
+// and aggregated into the median of the submissions that are still fresh
+pub trait Trait: system::Trait {
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	// identifier of a priced asset, shared with the lending markets
+	type AssetId: Parameter + Member + Default + Copy + MaybeSerializeDebug;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Oracle {
+                // accounts allowed to submit prices, managed by root
+                Feeders get(feeders) config(): Vec<T::AccountId>;
+
+                // number of blocks after which a price is stale
+                MaxPriceAge get(max_price_age) config(): T::BlockNumber;
+
+                // latest price submitted by each feeder, and the block it was submitted in
+                Submissions get(submission): map (T::AssetId, T::AccountId) => Option<(u128, T::BlockNumber)>;
+
+                // aggregated price of each asset, and the block it was last updated in
+                Prices get(price) config(prices): map T::AssetId => Option<(u128, T::BlockNumber)>;
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		// Initializing events
+		fn deposit_event<T>() = default;
+
+                fn on_initialize(_n: T::BlockNumber) {
+                    // a maximum price age is set in genesis and can never be
+                    // set to zero, so only chains upgraded from before the
+                    // oracle are without one
+                    if Self::max_price_age().is_zero() {
+                        Self::migrate_from_baseline();
+                    }
+                }
+
+                fn submit_price(_origin, asset: T::AssetId, price: u128) -> Result {
+                    let sender = ensure_signed(_origin)?;
+
+                    ensure!(Self::feeders().contains(&sender), "Sender is not an authorized feeder.");
+                    ensure!(price > 0, "Price must be non-zero.");
+
+                    let now = <system::Module<T>>::block_number();
+                    <Submissions<T>>::insert((asset, sender.clone()), (price, now));
+
+                    Self::deposit_event(RawEvent::PriceSubmitted(sender, asset, price));
+
+                    // at least the submission above is fresh
+                    if let Some(median) = Self::aggregate(asset) {
+                        <Prices<T>>::insert(asset, (median, now));
+                        Self::deposit_event(RawEvent::PriceUpdated(asset, median));
+                    }
+
+                    Ok(())
+                }
+
+                fn add_feeder(origin, who: T::AccountId) -> Result {
+                    ensure_root(origin)?;
+
+                    ensure!(!Self::feeders().contains(&who), "Account is already a feeder.");
+                    <Feeders<T>>::mutate(|feeders| feeders.push(who.clone()));
+
+                    Self::deposit_event(RawEvent::FeederAdded(who));
+
+                    Ok(())
+                }
+
+                fn remove_feeder(origin, who: T::AccountId) -> Result {
+                    ensure_root(origin)?;
+
+                    ensure!(Self::feeders().contains(&who), "Account is not a feeder.");
+                    // submissions of a removed feeder are left in place but
+                    // ignored, since only current feeders are aggregated
+                    <Feeders<T>>::mutate(|feeders| feeders.retain(|feeder| *feeder != who));
+
+                    Self::deposit_event(RawEvent::FeederRemoved(who));
+
+                    Ok(())
+                }
+
+                fn set_max_price_age(origin, max_price_age: T::BlockNumber) -> Result {
+                    ensure_root(origin)?;
+
+                    ensure!(!max_price_age.is_zero(), "Maximum price age must be non-zero.");
+                    <MaxPriceAge<T>>::put(max_price_age);
+
+                    Self::deposit_event(RawEvent::MaxPriceAgeUpdated(max_price_age));
+
+                    Ok(())
+                }
+	}
+}
+
+impl<T: Trait> Module<T> {
+    // the baseline runtime only lent the native asset, so its positions are
+    // valued in it: the native asset is priced at parity, which stands until
+    // root adds feeders to price it against later markets
+    fn migrate_from_baseline() {
+        let max_price_age = <T::BlockNumber as As<u64>>::sa(BASELINE_MAX_PRICE_AGE);
+        <MaxPriceAge<T>>::put(max_price_age);
+        Self::deposit_event(RawEvent::MaxPriceAgeUpdated(max_price_age));
+
+        let native = T::AssetId::default();
+        if !<Prices<T>>::exists(native) {
+            <Prices<T>>::insert(native, (PRICE_ONE, <system::Module<T>>::block_number()));
+            Self::deposit_event(RawEvent::PriceUpdated(native, PRICE_ONE));
+        }
+    }
+
+    // whether a price updated in `block` is still within the maximum age
+    pub fn is_recent(block: T::BlockNumber) -> bool {
+        let now = <T::BlockNumber as As<u64>>::as_(<system::Module<T>>::block_number());
+        let age = now.saturating_sub(<T::BlockNumber as As<u64>>::as_(block));
+        age <= <T::BlockNumber as As<u64>>::as_(Self::max_price_age())
+    }
+
+    // median of the fresh submissions from current feeders,
+    // averaging the middle pair when their number is even
+    fn aggregate(asset: T::AssetId) -> Option<u128> {
+        let mut prices: Vec<u128> = Self::feeders()
+            .into_iter()
+            .filter_map(|feeder| Self::submission((asset, feeder)))
+            .filter(|(_, block)| Self::is_recent(*block))
+            .map(|(price, _)| price)
+            .collect();
+
+        if prices.is_empty() {
+            return None;
+        }
+
+        prices.sort();
+        let middle = prices.len() / 2;
+        if prices.len() % 2 == 1 {
+            Some(prices[middle])
+        } else {
+            let (low, high) = (prices[middle - 1], prices[middle]);
+            Some(low / 2 + high / 2 + (low % 2 + high % 2) / 2)
+        }
+    }
+}
+
+impl<T: Trait> PriceFeed<T::AssetId> for Module<T> {
+    fn price(asset: T::AssetId) -> Option<u128> {
+        <Prices<T>>::get(asset).map(|(price, _)| price)
+    }
+
+    fn is_fresh(asset: T::AssetId) -> bool {
+        <Prices<T>>::get(asset).map_or(false, |(_, block)| Self::is_recent(block))
+    }
+}
+
+decl_event!(
+	pub enum Event<T>
+        where
+            <T as system::Trait>::AccountId,
+            <T as system::Trait>::BlockNumber,
+            <T as Trait>::AssetId,
+        {
+                // feeder, asset, submitted price
+                PriceSubmitted(AccountId, AssetId, u128),
+                // asset, aggregated price
+                PriceUpdated(AssetId, u128),
+                FeederAdded(AccountId),
+                FeederRemoved(AccountId),
+                MaxPriceAgeUpdated(BlockNumber),
+	}
+);
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use runtime_io::with_externalities;
+	use primitives::{H256, Blake2Hasher};
+	use support::{
+            impl_outer_origin,
+            assert_ok,
+            assert_noop
+        };
+	use runtime_primitives::{
+		BuildStorage,
+		traits::{BlakeTwo256, IdentityLookup, OnInitialize},
+		testing::{Digest, DigestItem, Header}
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for Test {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+
+	impl system::Trait for Test {
+		type Origin = Origin;
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type Digest = Digest;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type Log = DigestItem;
+	}
+
+	impl super::Trait for Test {
+		type Event = ();
+		type AssetId = u32;
+	}
+
+	type Oracle = Module<Test>;
+
+	fn build() -> runtime_io::TestExternalities<Blake2Hasher> {
+		let mut t = system::GenesisConfig::<Test>::default()
+                    .build_storage()
+                    .unwrap()
+                    .0;
+                t.extend(
+                    GenesisConfig::<Test> {
+                        feeders: vec![1, 2, 3],
+                        max_price_age: 10,
+                        prices: vec![(0, (1_000, 0))],
+                    }
+                    .build_storage()
+                    .unwrap()
+                    .0,
+                );
+                t.into()
+	}
+
+        #[test]
+        fn only_feeders_can_submit_prices() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Oracle::submit_price(Origin::signed(4), 1, 500),
+                             "Sender is not an authorized feeder.");
+                assert_noop!(Oracle::submit_price(Origin::signed(1), 1, 0),
+                             "Price must be non-zero.");
+
+                assert_ok!(Oracle::submit_price(Origin::signed(1), 1, 500));
+                assert_eq!(Oracle::price(1), Some((500, 0)));
+            });
+        }
+
+        #[test]
+        fn price_is_median_of_submissions() {
+            with_externalities(&mut build(), || {
+                <system::Module<Test>>::set_block_number(1);
+                assert_ok!(Oracle::submit_price(Origin::signed(1), 1, 500));
+                assert_ok!(Oracle::submit_price(Origin::signed(2), 1, 700));
+                assert_eq!(Oracle::price(1), Some((600, 1)));
+
+                assert_ok!(Oracle::submit_price(Origin::signed(3), 1, 10_000));
+                assert_eq!(Oracle::price(1), Some((700, 1)));
+
+                // an outlier resubmitting cannot move the median alone
+                assert_ok!(Oracle::submit_price(Origin::signed(3), 1, 1));
+                assert_eq!(Oracle::price(1), Some((500, 1)));
+            });
+        }
+
+        #[test]
+        fn stale_submissions_are_not_aggregated() {
+            with_externalities(&mut build(), || {
+                <system::Module<Test>>::set_block_number(1);
+                assert_ok!(Oracle::submit_price(Origin::signed(1), 1, 500));
+
+                <system::Module<Test>>::set_block_number(12);
+                assert_ok!(Oracle::submit_price(Origin::signed(2), 1, 700));
+                assert_eq!(Oracle::price(1), Some((700, 12)));
+            });
+        }
+
+        #[test]
+        fn prices_go_stale_after_max_age() {
+            with_externalities(&mut build(), || {
+                assert_eq!(<Oracle as PriceFeed<u32>>::price(0), Some(1_000));
+                assert!(<Oracle as PriceFeed<u32>>::is_fresh(0));
+
+                <system::Module<Test>>::set_block_number(11);
+                assert!(!<Oracle as PriceFeed<u32>>::is_fresh(0));
+                // a stale price is still reported, for valuing existing positions
+                assert_eq!(<Oracle as PriceFeed<u32>>::price(0), Some(1_000));
+
+                assert!(!<Oracle as PriceFeed<u32>>::is_fresh(1));
+                assert_eq!(<Oracle as PriceFeed<u32>>::price(1), None);
+            });
+        }
+
+        #[test]
+        fn root_manages_feeders() {
+            with_externalities(&mut build(), || {
+                assert_noop!(Oracle::add_feeder(Origin::signed(1), 4),
+                             "bad origin: expected to be a root origin");
+                assert_ok!(Oracle::add_feeder(Origin::ROOT, 4));
+                assert_noop!(Oracle::add_feeder(Origin::ROOT, 4), "Account is already a feeder.");
+                assert_ok!(Oracle::submit_price(Origin::signed(4), 1, 500));
+
+                assert_ok!(Oracle::remove_feeder(Origin::ROOT, 4));
+                assert_eq!(Oracle::feeders(), vec![1, 2, 3]);
+                assert_noop!(Oracle::submit_price(Origin::signed(4), 1, 500),
+                             "Sender is not an authorized feeder.");
+
+                assert_noop!(Oracle::set_max_price_age(Origin::ROOT, 0),
+                             "Maximum price age must be non-zero.");
+                assert_ok!(Oracle::set_max_price_age(Origin::ROOT, 20));
+                assert_eq!(Oracle::max_price_age(), 20);
+            });
+        }
+
+        #[test]
+        fn chains_without_an_oracle_price_the_native_asset() {
+            with_externalities(&mut build(), || {
+                // genesis already configured the oracle
+                <Oracle as OnInitialize<u64>>::on_initialize(1);
+                assert_eq!(Oracle::max_price_age(), 10);
+                assert_eq!(Oracle::price(0), Some((1_000, 0)));
+
+                // as a chain of the baseline runtime reads
+                <MaxPriceAge<Test>>::kill();
+                <Prices<Test>>::remove(0);
+                <system::Module<Test>>::set_block_number(5);
+
+                <Oracle as OnInitialize<u64>>::on_initialize(5);
+                assert_eq!(Oracle::max_price_age(), 360);
+                assert_eq!(Oracle::price(0), Some((PRICE_ONE, 5)));
+                assert!(<Oracle as PriceFeed<u32>>::is_fresh(0));
+            });
+        }
+}
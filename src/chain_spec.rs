@@ -2,7 +2,7 @@ use primitives::{ed25519, sr25519, Pair};
 use lending_runtime::{
 	AccountId, GenesisConfig, ConsensusConfig, TimestampConfig, BalancesConfig,
 	SudoConfig, IndicesConfig,
-        LendingConfig
+        AssetsConfig, OracleConfig, LendingConfig, Market, Perbill, Lending
 };
 use substrate_service;
 
@@ -95,6 +95,12 @@ impl Alternative {
 }
 
 fn testnet_genesis(initial_authorities: Vec<AuthorityId>, endowed_accounts: Vec<AccountId>, root_key: AccountId) -> GenesisConfig {
+        // the lending pool and the collateral vault hold the existential
+        // deposit, so that neither is reaped as funds leave it. only what
+        // is supplied is ever lent, so the pool's 500 of each asset is no
+        // liquidity, and only covers rounding in the accounts' favour
+        let pool = Lending::pool_account();
+        let vault = Lending::collateral_account();
 	GenesisConfig {
 		consensus: Some(ConsensusConfig {
 			code: include_bytes!("../runtime/wasm/target/wasm32-unknown-unknown/release/lending_runtime_wasm.compact.wasm").to_vec(),
@@ -113,14 +119,55 @@ fn testnet_genesis(initial_authorities: Vec<AuthorityId>, endowed_accounts: Vec<
 			existential_deposit: 500,
 			transfer_fee: 0,
 			creation_fee: 0,
-			balances: endowed_accounts.iter().cloned().map(|k|(k, 1_000_000)).collect(),
+			balances: endowed_accounts.iter().cloned().map(|k|(k, 1_000_000))
+				.chain(vec![(pool.clone(), 500), (vault, 500)])
+				.collect(),
 			vesting: vec![],
 		}),
 		sudo: Some(SudoConfig {
 			key: root_key,
 		}),
+                assets: Some(AssetsConfig {
+                    // a second asset to borrow against the native currency
+                    balances: endowed_accounts.iter().cloned().map(|k|((1, k), 1_000_000))
+                        .chain(Some(((1, pool), 500)))
+                        .collect(),
+                }),
+                oracle: Some(OracleConfig {
+                    feeders: vec![account_key("Alice")],
+                    // an hour of blocks before prices must be fed again
+                    max_price_age: 360,
+                    // both assets start at parity, with prices scaled by one billion
+                    prices: vec![(0, (1_000_000_000, 0)), (1, (1_000_000_000, 0))],
+                }),
                 lending: Some(LendingConfig {
-                    liquidity_provider: account_key("Alice"),
+                    markets: vec![
+                        // the native currency
+                        (0, Market {
+                            share_asset: 100,
+                            collateral_factor: Perbill::from_percent(75),
+                            reserve_factor: Perbill::from_percent(10),
+                            // annual rates
+                            base_rate: Perbill::from_percent(2),
+                            multiplier: Perbill::from_percent(10),
+                            jump_multiplier: Perbill::from_percent(100),
+                            kink: Perbill::from_percent(80),
+                        }),
+                        (1, Market {
+                            share_asset: 101,
+                            collateral_factor: Perbill::from_percent(60),
+                            reserve_factor: Perbill::from_percent(20),
+                            base_rate: Perbill::from_percent(2),
+                            multiplier: Perbill::from_percent(15),
+                            jump_multiplier: Perbill::from_percent(100),
+                            kink: Perbill::from_percent(70),
+                        }),
+                    ],
+                    close_factor: Perbill::from_percent(50),
+                    liquidation_incentive: Perbill::from_percent(8),
+                    // a week's grace, then 10% a year on top of the market rate
+                    grace_period: 7 * 24 * 60 * 60,
+                    penalty_rate: Perbill::from_percent(10),
                 }),
 	}
 }
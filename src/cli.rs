@@ -1,4 +1,4 @@
-use crate::service;
+use crate::{service, rpc};
 use futures::{future, Future, sync::oneshot};
 use std::cell::RefCell;
 use tokio::runtime::Runtime;
@@ -26,17 +26,21 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 			info!("Roles: {:?}", config.roles);
 			let runtime = Runtime::new().map_err(|e| format!("{:?}", e))?;
 			let executor = runtime.executor();
+			// the lending RPC follows the stock RPC's command line options
+			let rpc_address = rpc::address(config.rpc_http);
+			let rpc_cors = config.rpc_cors.clone();
 			match config.roles {
-				ServiceRoles::LIGHT => run_until_exit(
-					runtime,
-				 	service::Factory::new_light(config, executor).map_err(|e| format!("{:?}", e))?,
-					exit
-				),
-				_ => run_until_exit(
-					runtime,
-					service::Factory::new_full(config, executor).map_err(|e| format!("{:?}", e))?,
-					exit
-				),
+				ServiceRoles::LIGHT => {
+					let service = service::Factory::new_light(config, executor).map_err(|e| format!("{:?}", e))?;
+					// kept alive until the node exits
+					let _rpc = rpc_address.and_then(|address| rpc::start_http(service.client(), address, rpc_cors));
+					run_until_exit(runtime, service, exit)
+				},
+				_ => {
+					let service = service::Factory::new_full(config, executor).map_err(|e| format!("{:?}", e))?;
+					let _rpc = rpc_address.and_then(|address| rpc::start_http(service.client(), address, rpc_cors));
+					run_until_exit(runtime, service, exit)
+				},
 			}.map_err(|e| format!("{:?}", e))
 		}
 	).map_err(Into::into).map(|_| ())
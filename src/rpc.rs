@@ -0,0 +1,184 @@
+//! Lending JSON-RPC endpoints, backed by the runtime's `LendingApi`.
+//!
+//! The RPC server started by `substrate_service` cannot be extended at this
+//! Substrate revision, so these methods are served by a second HTTP server.
+
+use std::{net::SocketAddr, sync::Arc};
+use log::{info, warn};
+use jsonrpc_core::{Error, ErrorCode, IoHandler, Result};
+use jsonrpc_derive::rpc;
+use jsonrpc_http_server::{AccessControlAllowOrigin, DomainsValidation, Server, ServerBuilder};
+use serde::Serialize;
+use substrate_client::{self as client, Client, CallExecutor, backend::Backend, runtime_api::ProvideRuntimeApi};
+use primitives::{Blake2Hasher, crypto::Ss58Codec};
+use runtime_primitives::generic::BlockId;
+use lending_runtime::{
+	AccountId, AssetId, Balance, BlockNumber, Hash, Fixed,
+	AccountSummary, MarketSummary, PositionId, PositionKind, LendingApi, opaque::Block,
+};
+
+/// Rates of a market as of a block.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rates {
+	/// Block the rates were read at.
+	pub block: BlockNumber,
+	/// Borrows over supplied liquidity.
+	pub utilization: Fixed,
+	/// Annual rate earned by suppliers.
+	pub supply_rate: Fixed,
+	/// Annual rate paid by borrowers.
+	pub borrow_rate: Fixed,
+}
+
+/// An open position, as listed by `lending_accounts`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenPosition {
+	pub id: PositionId,
+	/// SS58 address of the owner.
+	pub owner: String,
+	pub asset: AssetId,
+	pub kind: PositionKind,
+}
+
+/// Lending queries, named `lending_*` over JSON-RPC.
+#[rpc]
+pub trait LendingRpc {
+	/// Balances of an account in each market, the maturity of its fixed-term borrows and
+	/// whether they are in default, and its health factor, at the best block unless `at`
+	/// is given.
+	#[rpc(name = "lending_accountSummary")]
+	fn account_summary(&self, who: String, at: Option<Hash>) -> Result<Option<AccountSummary<AssetId, Balance>>>;
+
+	/// Aggregates, rates and utilization of a market, at the best block unless `at` is given.
+	#[rpc(name = "lending_marketSummary")]
+	fn market_summary(&self, asset: AssetId, at: Option<Hash>) -> Result<Option<MarketSummary<Balance>>>;
+
+	/// Rates of a market as of the given block number.
+	#[rpc(name = "lending_ratesAt")]
+	fn rates_at(&self, asset: AssetId, block: BlockNumber) -> Result<Option<Rates>>;
+
+	/// Open positions from slot `start` of the registry on, at most `limit` of them, at the
+	/// best block unless `at` is given. Page at a fixed `at` to enumerate every position.
+	#[rpc(name = "lending_accounts")]
+	fn accounts(&self, start: u64, limit: u64, at: Option<Hash>) -> Result<Vec<OpenPosition>>;
+}
+
+/// Serves `LendingRpc` from a full or light client.
+pub struct Lending<B, E, RA> {
+	client: Arc<Client<B, E, Block, RA>>,
+}
+
+impl<B, E, RA> Lending<B, E, RA> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+{
+	/// Serve queries from `client`.
+	pub fn new(client: Arc<Client<B, E, Block, RA>>) -> Self {
+		Lending { client }
+	}
+
+	fn block_or_best(&self, at: Option<Hash>) -> Result<BlockId<Block>> {
+		match at {
+			Some(hash) => Ok(BlockId::hash(hash)),
+			None => self.client.info()
+				.map(|info| BlockId::hash(info.chain.best_hash))
+				.map_err(client_error),
+		}
+	}
+}
+
+impl<B, E, RA> LendingRpc for Lending<B, E, RA> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: LendingApi<Block>,
+{
+	fn account_summary(&self, who: String, at: Option<Hash>) -> Result<Option<AccountSummary<AssetId, Balance>>> {
+		let who = AccountId::from_ss58check(&who)
+			.map_err(|_| Error::invalid_params("Account is not a valid SS58 address."))?;
+		let at = self.block_or_best(at)?;
+
+		self.client.runtime_api().account_summary(&at, who).map_err(client_error)
+	}
+
+	fn market_summary(&self, asset: AssetId, at: Option<Hash>) -> Result<Option<MarketSummary<Balance>>> {
+		let at = self.block_or_best(at)?;
+
+		self.client.runtime_api().market_summary(&at, asset).map_err(client_error)
+	}
+
+	fn rates_at(&self, asset: AssetId, block: BlockNumber) -> Result<Option<Rates>> {
+		let summary = self.client.runtime_api()
+			.market_summary(&BlockId::number(block), asset)
+			.map_err(client_error)?;
+
+		Ok(summary.map(|summary| Rates {
+			block,
+			utilization: summary.utilization,
+			supply_rate: summary.supply_rate,
+			borrow_rate: summary.borrow_rate,
+		}))
+	}
+
+	fn accounts(&self, start: u64, limit: u64, at: Option<Hash>) -> Result<Vec<OpenPosition>> {
+		let at = self.block_or_best(at)?;
+		let positions = self.client.runtime_api().accounts(&at, start, limit).map_err(client_error)?;
+
+		Ok(positions.into_iter().map(|(id, position)| OpenPosition {
+			id,
+			owner: position.owner.to_ss58check(),
+			asset: position.asset,
+			kind: position.kind,
+		}).collect())
+	}
+}
+
+fn client_error(e: client::error::Error) -> Error {
+	Error {
+		code: ErrorCode::ServerError(1),
+		message: "Lending runtime API call failed.".into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+/// Address the lending RPC listens on: the interface of the stock HTTP RPC, which
+/// `--rpc-external` opens up, at the port after its `--rpc-port`. 9934 by default.
+pub fn address(rpc_http: Option<SocketAddr>) -> Option<SocketAddr> {
+	rpc_http.and_then(|address| address.port().checked_add(1)
+		.map(|port| SocketAddr::new(address.ip(), port)))
+}
+
+/// Start the lending RPC at `address`, allowing the origins in `cors` as `--rpc-cors`
+/// does for the stock RPC, and return the running server.
+/// The node keeps running without it if the port cannot be bound.
+pub fn start_http<B, E, RA>(
+	client: Arc<Client<B, E, Block, RA>>,
+	address: SocketAddr,
+	cors: Option<Vec<String>>,
+) -> Option<Server> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: LendingApi<Block>,
+{
+	let cors: Option<Vec<AccessControlAllowOrigin>> = cors
+		.map(|origins| origins.into_iter().map(Into::into).collect());
+	let mut io = IoHandler::new();
+	io.extend_with(Lending::new(client).to_delegate());
+
+	match ServerBuilder::new(io).cors(DomainsValidation::from(cors)).start_http(&address) {
+		Ok(server) => {
+			info!("Lending RPC listening on http://{}", address);
+			Some(server)
+		},
+		Err(e) => {
+			warn!("Unable to start lending RPC on {}: {}", address, e);
+			None
+		},
+	}
+}